@@ -0,0 +1,79 @@
+use std::collections::BTreeMap;
+
+use crate::regions::Regions;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum AnnotationKind {
+    Label,
+    Comment,
+}
+
+/// User labels, comments and data regions of one image, keyed by address
+#[derive(Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Annotations {
+    labels: BTreeMap<u64, String>,
+    comments: BTreeMap<u64, String>,
+    /// Regions marked by the user, they override regions from ELF sections
+    pub regions: Regions,
+}
+
+impl Annotations {
+    pub fn get(&self, kind: AnnotationKind, addr: u64) -> Option<&str> {
+        match kind {
+            AnnotationKind::Label => self.labels.get(&addr),
+            AnnotationKind::Comment => self.comments.get(&addr),
+        }
+        .map(String::as_str)
+    }
+
+    /// Empty text removes the annotation. Labels have to be unique
+    /// single words as they are used as go-to targets.
+    pub fn set(&mut self, kind: AnnotationKind, addr: u64, text: &str) -> Result<(), String> {
+        let text = text.trim();
+        let map = match kind {
+            AnnotationKind::Label => {
+                if text.contains(char::is_whitespace) {
+                    return Err("a label can't contain spaces".to_string());
+                }
+                if self
+                    .find_label(text)
+                    .is_some_and(|label_addr| label_addr != addr)
+                {
+                    return Err(format!("label {text} already exists"));
+                }
+                &mut self.labels
+            }
+            AnnotationKind::Comment => &mut self.comments,
+        };
+        if text.is_empty() {
+            map.remove(&addr);
+        } else {
+            map.insert(addr, text.to_string());
+        }
+        Ok(())
+    }
+
+    /// Address of the label
+    pub fn find_label(&self, name: &str) -> Option<u64> {
+        self.labels
+            .iter()
+            .find(|(_, label)| *label == name)
+            .map(|(addr, _)| *addr)
+    }
+
+    /// Label and comment at the address joined for searching
+    pub fn search_text(&self, addr: u64) -> String {
+        let label = self
+            .labels
+            .get(&addr)
+            .map(String::as_str)
+            .unwrap_or_default();
+        let comment = self
+            .comments
+            .get(&addr)
+            .map(String::as_str)
+            .unwrap_or_default();
+        format!("{label} {comment}")
+    }
+}
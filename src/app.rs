@@ -1,9 +1,19 @@
-use eframe;
+use std::collections::BTreeMap;
+
 use egui::Modifiers;
 
 use crate::{
-    console::Console, instr_decoder::InstrDecoder, instr_list::InstrList, load_demo::LoadDemo,
-    sim::Simulator,
+    breakpoints::Breakpoints,
+    console::Console,
+    device_tree::DeviceTree,
+    elf::{self, Section, Symbol},
+    instr_decoder::InstrDecoder,
+    instr_list::InstrList,
+    isa_reference::IsaReference,
+    load_binary::{read_binary, Binary, BinaryLoader, FileWatcher},
+    load_demo::{Demo, DemoWindow, LoadDemo},
+    number::parse_addr,
+    sim::{MachineConfig, SimEvent, Simulator},
 };
 
 /// Deserialize/Serialize so we can persist app state on shutdown.
@@ -16,9 +26,27 @@ pub struct KompusimApp {
     instr_list: InstrList,
     decode_instr: InstrDecoder,
     console: Console,
+    device_tree: DeviceTree,
+    isa_reference: IsaReference,
+    /// Reload the binary when it changes on disk (native only)
+    auto_reload: bool,
+    /// Continue execution (to the first breakpoint) after the automatic reload
+    run_after_reload: bool,
     #[serde(skip)] // this how you opt-out of serialization of a member
     load_demo: LoadDemo,
     #[serde(skip)]
+    binary_loader: BinaryLoader,
+    /// The last loaded image and the machine it runs on
+    #[serde(skip)]
+    loaded_image: Option<LoadedImage>,
+    /// Watches the file of the loaded binary
+    #[serde(skip)]
+    file_watcher: Option<FileWatcher>,
+    #[serde(skip)]
+    machine: MachineConfig,
+    #[serde(skip)]
+    breakpoints: Breakpoints,
+    #[serde(skip)]
     sim: Simulator,
 }
 
@@ -31,6 +59,15 @@ impl Default for KompusimApp {
             decode_instr: InstrDecoder::default(),
             load_demo: LoadDemo::default(),
             console: Console::default(),
+            device_tree: DeviceTree::default(),
+            isa_reference: IsaReference::default(),
+            auto_reload: true,
+            run_after_reload: false,
+            binary_loader: BinaryLoader::default(),
+            loaded_image: None,
+            file_watcher: None,
+            machine: MachineConfig::default(),
+            breakpoints: Breakpoints::default(),
             sim: Simulator::new(),
         }
     }
@@ -42,12 +79,144 @@ impl KompusimApp {
         // Start simulator thread
         //thread
         // Load previous app state (if any).
-        if let Some(storage) = cc.storage {
+        let mut app: KompusimApp = if let Some(storage) = cc.storage {
             let app: KompusimApp = eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default();
             set_all_fonts_size(&cc.egui_ctx, app.font_delta as f32 * 0.5);
-            return app;
+            app
+        } else {
+            Default::default()
+        };
+        #[cfg(target_arch = "wasm32")]
+        app.apply_session_params(&cc.integration_info.web_info.location.query_map);
+        // native: the same parameters as command line arguments
+        #[cfg(not(target_arch = "wasm32"))]
+        app.apply_session_params(
+            &std::env::args()
+                .skip(1)
+                .filter_map(|arg| {
+                    let (key, value) = arg.split_once('=')?;
+                    Some((key.to_string(), value.to_string()))
+                })
+                .collect(),
+        );
+        app
+    }
+
+    /// Set up the session from the web page URL query parameters, e.g.:
+    /// ?demo=uart_hello_world&run=1&bp=0x80000010,0x80000020
+    fn apply_session_params(&mut self, params: &BTreeMap<String, String>) {
+        if let Some(id) = params.get("demo") {
+            match self.load_demo.find(id) {
+                Some(demo) => {
+                    self.load_demo.close();
+                    let image = LoadedImage::from_demo(demo);
+                    self.machine = demo.machine;
+                    reset_and_load(
+                        &mut self.sim,
+                        &mut self.machine,
+                        &image,
+                        &mut self.instr_list,
+                        &mut self.device_tree,
+                    );
+                    self.loaded_image = Some(image);
+                    open_demo_windows(
+                        demo,
+                        &mut self.instr_list,
+                        &mut self.decode_instr,
+                        &mut self.console,
+                    );
+                }
+                None => println!("Unknown demo in the URL: {}", id),
+            }
+        }
+        if let Some(breakpoints) = params.get("bp") {
+            for bp in breakpoints.split(',') {
+                match parse_addr(bp) {
+                    Some(addr) => self.breakpoints.add(addr),
+                    None => println!("Invalid breakpoint address in the URL: {}", bp),
+                }
+            }
+        }
+        if params
+            .get("run")
+            .is_some_and(|run| run == "1" || run == "true")
+        {
+            // the simulator gets breakpoints in update() which runs later
+            if let Some(sim_breakpoints) = self.breakpoints.take_changed() {
+                self.sim.set_breakpoints(sim_breakpoints);
+            }
+            self.sim.carry_on();
         }
-        Default::default()
+    }
+}
+
+/// Program loaded into the machine, kept to reload it on reset
+struct LoadedImage {
+    name: String,
+    bytes: Vec<u8>,
+    load_addr: u64,
+    entry: u64,
+    /// Sections and symbols of ELF files
+    sections: Vec<Section>,
+    symbols: Vec<Symbol>,
+}
+
+impl LoadedImage {
+    fn from_demo(demo: &Demo) -> LoadedImage {
+        LoadedImage {
+            name: demo.name.to_string(),
+            bytes: demo.image.to_vec(),
+            load_addr: demo.load_addr,
+            entry: demo.entry,
+            sections: Vec::new(),
+            symbols: Vec::new(),
+        }
+    }
+
+    /// ELF files are loaded by their segments,
+    /// raw binaries are loaded and started at the beginning of RAM
+    fn from_binary(binary: Binary, machine: &MachineConfig) -> Result<LoadedImage, String> {
+        if !elf::is_elf(&binary.bytes) {
+            if binary.bytes.len() as u64 > MachineConfig::MAX_RAM_SIZE {
+                return Err(format!(
+                    "the binary is larger than the maximum RAM size 0x{:x}",
+                    MachineConfig::MAX_RAM_SIZE
+                ));
+            }
+            return Ok(LoadedImage {
+                name: binary.name,
+                bytes: binary.bytes,
+                load_addr: machine.ram_base,
+                entry: machine.ram_base,
+                sections: Vec::new(),
+                symbols: Vec::new(),
+            });
+        }
+        let elf = elf::parse(&binary.bytes)?;
+        let (load_addr, bytes) = elf.flat_image()?;
+        if load_addr < machine.ram_base {
+            return Err(format!(
+                "ELF segments at 0x{load_addr:x} are below RAM at 0x{:x}",
+                machine.ram_base
+            ));
+        }
+        let ram_needed = load_addr - machine.ram_base + bytes.len() as u64;
+        if ram_needed > MachineConfig::MAX_RAM_SIZE {
+            return Err(format!(
+                "ELF segments end 0x{ram_needed:x} bytes after the start of RAM at 0x{:x}, \
+                 more than the maximum RAM size 0x{:x}",
+                machine.ram_base,
+                MachineConfig::MAX_RAM_SIZE
+            ));
+        }
+        Ok(LoadedImage {
+            name: binary.name,
+            bytes,
+            load_addr,
+            entry: elf.entry,
+            sections: elf.sections,
+            symbols: elf.symbols,
+        })
     }
 }
 
@@ -68,6 +237,15 @@ impl eframe::App for KompusimApp {
             decode_instr,
             load_demo,
             console,
+            device_tree,
+            isa_reference,
+            auto_reload,
+            run_after_reload,
+            binary_loader,
+            loaded_image,
+            file_watcher,
+            machine,
+            breakpoints,
             sim,
         } = self;
 
@@ -94,13 +272,22 @@ impl eframe::App for KompusimApp {
                 ui.menu_button("File", |ui| {
                     // hack to make menus oneliners
                     ui.set_min_width(*font_delta as f32 * 10.0 + 150.0);
-                    if ui.button("Load binary (unimplemented)").clicked() {
+                    if ui.button("Load binary...").clicked() {
+                        binary_loader.pick_file(ctx);
                         ui.close_menu();
                     }
                     if ui.button("Load demo...").clicked() {
                         load_demo.open();
                         ui.close_menu();
                     }
+                    #[cfg(not(target_arch = "wasm32"))]
+                    {
+                        ui.checkbox(auto_reload, "Reload binary on change");
+                        ui.add_enabled(
+                            *auto_reload,
+                            egui::Checkbox::new(run_after_reload, "Run after reload"),
+                        );
+                    }
                     if ui.button("Settings").clicked() {
                         *show_settings = true;
                         ui.close_menu();
@@ -113,11 +300,20 @@ impl eframe::App for KompusimApp {
                 ui.menu_button("Run", |ui| {
                     // hack to make menus oneliners
                     ui.set_min_width(*font_delta as f32 * 10.0 + 150.0);
-                    if ui.button("Run/Continue (unimplemented)").clicked() {
+                    if ui.button("Run/Continue").clicked() {
                         sim.carry_on();
                         ui.close_menu();
                     }
-                    if ui.button("Step (unimplemented)").clicked() {
+                    if ui.button("Step").clicked() {
+                        sim.step();
+                        ui.close_menu();
+                    }
+                    if ui.button("Pause").clicked() {
+                        sim.pause();
+                        ui.close_menu();
+                    }
+                    if ui.button("Remove all breakpoints").clicked() {
+                        breakpoints.remove_all();
                         ui.close_menu();
                     }
                 });
@@ -136,6 +332,14 @@ impl eframe::App for KompusimApp {
                         console.open();
                         ui.close_menu();
                     }
+                    if ui.button("Device tree").clicked() {
+                        device_tree.open();
+                        ui.close_menu();
+                    }
+                    if ui.button("ISA reference").clicked() {
+                        isa_reference.open();
+                        ui.close_menu();
+                    }
                     if ui.button("Memory (unimplemented)").clicked() {
                         ui.close_menu();
                     }
@@ -189,14 +393,94 @@ impl eframe::App for KompusimApp {
             ui.label("A RISC-V ISA simulator with focus on education and debug capabilities");
             ui.hyperlink("https://github.com/dvoytik/kompusim-gui");
             egui::warn_if_debug_build(ui);
+            ui.separator();
+            match loaded_image {
+                Some(image) => ui.label(format!("Loaded: {}", image.name)),
+                None => ui.label("Load a demo or drop a binary file here"),
+            };
         });
 
-        instr_list.show(ctx);
-        decode_instr.show(ctx);
-        if let Some(demo_bin) = load_demo.show(ctx) {
-            sim.load_image(0x0000000080000000, demo_bin) // TODO: remove hard-coded address
+        while let Some(event) = sim.event_recv() {
+            match event {
+                SimEvent::Memory { addr, data } => instr_list.memory_received(addr, data),
+                SimEvent::Stopped { pc, stepped, x } => instr_list.sim_stopped(pc, stepped, *x),
+                SimEvent::Running => instr_list.sim_running(),
+            }
+        }
+        instr_list.show(ctx, breakpoints);
+        for (addr, len) in instr_list.take_mem_requests() {
+            sim.read_memory(addr, len);
+        }
+        if let Some(sim_breakpoints) = breakpoints.take_changed() {
+            sim.set_breakpoints(sim_breakpoints);
+        }
+        if let Some((instr, addr)) = instr_list.take_decode_request() {
+            decode_instr.decode_at(instr, addr);
+        }
+        decode_instr.show(ctx, instr_list);
+        isa_reference.show(ctx);
+        let mut new_binary = binary_loader.poll(ctx);
+        let mut reloaded = false;
+        if let (true, Some(watcher)) = (*auto_reload, file_watcher.as_mut()) {
+            ctx.request_repaint_after(FileWatcher::POLL_INTERVAL);
+            if watcher.changed() {
+                match read_binary(watcher.path().to_path_buf()) {
+                    Ok(binary) => {
+                        println!("Reloading changed {}", watcher.path().display());
+                        new_binary = Some(binary);
+                        reloaded = true;
+                    }
+                    Err(err) => println!("Failed to reload binary: {}", err),
+                }
+            }
+        }
+        if let Some(binary) = new_binary {
+            *file_watcher = binary.path.clone().map(FileWatcher::new);
+            // the device tree address chosen by the user is kept
+            *machine = MachineConfig {
+                dtb_addr: machine.dtb_addr,
+                ..MachineConfig::default()
+            };
+            match LoadedImage::from_binary(binary, machine) {
+                Ok(image) => {
+                    // the image has to fit into RAM
+                    let image_end = image.load_addr - machine.ram_base + image.bytes.len() as u64;
+                    machine.ram_size = machine.ram_size.max((image_end + 0xfff) & !0xfff);
+                    // breakpoints are kept by the simulator across resets,
+                    // they move with their symbols in a rebuilt image
+                    reset_and_load(sim, machine, &image, instr_list, device_tree);
+                    breakpoints.set_symbols(&image.symbols);
+                    if let Some(sim_breakpoints) = breakpoints.take_changed() {
+                        sim.set_breakpoints(sim_breakpoints);
+                    }
+                    *loaded_image = Some(image);
+                    if reloaded && *run_after_reload {
+                        sim.carry_on();
+                    }
+                }
+                Err(err) => println!("Failed to load binary: {}", err),
+            }
+        }
+        if let Some(demo) = load_demo.show(ctx) {
+            *file_watcher = None;
+            *machine = demo.machine;
+            let image = LoadedImage::from_demo(demo);
+            reset_and_load(sim, machine, &image, instr_list, device_tree);
+            *loaded_image = Some(image);
+            open_demo_windows(demo, instr_list, decode_instr, console);
         }
         console.show(ctx, sim.console_recv());
+        if let Some(dtb_addr) = device_tree.show(ctx, machine) {
+            machine.dtb_addr = dtb_addr;
+            match loaded_image {
+                Some(image) => reset_and_load(sim, machine, image, instr_list, device_tree),
+                None => {
+                    let ram_start = machine.ram_base..machine.ram_base;
+                    device_tree.set_dtb_placement(machine.place_dtb(ram_start));
+                    sim.reset(*machine);
+                }
+            }
+        }
 
         egui::Window::new("Settings")
             .open(show_settings)
@@ -206,6 +490,39 @@ impl eframe::App for KompusimApp {
     }
 }
 
+/// Reset the machine and load the image into it
+/// The DTB is moved or RAM grown if they don't fit next to the image
+fn reset_and_load(
+    sim: &mut Simulator,
+    machine: &mut MachineConfig,
+    image: &LoadedImage,
+    instr_list: &mut InstrList,
+    device_tree: &mut DeviceTree,
+) {
+    let image_range = image.load_addr..image.load_addr + image.bytes.len() as u64;
+    device_tree.set_dtb_placement(machine.place_dtb(image_range));
+    sim.reset(*machine);
+    sim.load_image(image.load_addr, image.bytes.clone());
+    sim.set_pc(image.entry);
+    instr_list.set_image(&image.name, image.load_addr, image.bytes.len() as u64);
+    instr_list.set_elf_info(&image.sections, &image.symbols);
+}
+
+fn open_demo_windows(
+    demo: &Demo,
+    instr_list: &mut InstrList,
+    decode_instr: &mut InstrDecoder,
+    console: &mut Console,
+) {
+    for window in demo.windows {
+        match window {
+            DemoWindow::InstrList => instr_list.open(),
+            DemoWindow::InstrDecoder => decode_instr.open(),
+            DemoWindow::Console => console.open(),
+        }
+    }
+}
+
 fn increase_all_fonts(ctx: &egui::Context, font_delta: &mut i32) {
     if *font_delta <= 50 {
         *font_delta += 1;
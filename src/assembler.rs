@@ -0,0 +1,295 @@
+//! Assembler of single instructions of RV64I, M, A, Zicsr, Zifencei and
+//! the privileged set. Branch and jump targets are offsets from the instruction.
+
+use crate::{
+    instr_format::opcode,
+    isa::{Extension, CSR_NAMES, INSTRS},
+    number::parse_int,
+    regs::parse_reg,
+};
+
+/// Encode one instruction, e.g., "addi a0, a0, 1" or "beq t0, t1, -8"
+pub fn assemble(text: &str) -> Result<u32, String> {
+    let text = text.trim();
+    let (mnemonic, operands) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    let mnemonic = mnemonic.to_lowercase();
+    // atomics have optional memory ordering suffixes
+    let (name, aqrl) = [(".aqrl", 0b11), (".aq", 0b10), (".rl", 0b01)]
+        .iter()
+        .find_map(|(suffix, bits)| Some((mnemonic.strip_suffix(suffix)?, *bits)))
+        .unwrap_or((&mnemonic, 0));
+    let def = INSTRS
+        .iter()
+        .find(|def| def.name == name)
+        .filter(|def| aqrl == 0 || def.ext == Extension::A)
+        .ok_or_else(|| format!("unknown instruction '{mnemonic}'"))?;
+    let ops: Vec<&str> = if operands.trim().is_empty() {
+        Vec::new()
+    } else {
+        operands.split(',').map(str::trim).collect()
+    };
+
+    let fields = match def.value & 0x7f {
+        _ if def.mask == 0xffff_ffff => {
+            expect(&ops, 0, "")?;
+            0
+        }
+        opcode::MISC_MEM if def.name == "fence" => match ops.len() {
+            0 => fence_set("iorw")? << 24 | fence_set("iorw")? << 20,
+            _ => {
+                expect(&ops, 2, "pred, succ")?;
+                fence_set(ops[0])? << 24 | fence_set(ops[1])? << 20
+            }
+        },
+        opcode::MISC_MEM => {
+            expect(&ops, 0, "")?;
+            0
+        }
+        opcode::SYSTEM if def.name == "sfence.vma" => match ops.len() {
+            0 => 0,
+            1 => rs1(ops[0])?,
+            _ => {
+                expect(&ops, 2, "rs1, rs2")?;
+                rs1(ops[0])? | rs2(ops[1])?
+            }
+        },
+        opcode::SYSTEM => {
+            // csrrwi, csrrsi and csrrci have funct3 >= 5
+            if def.value >> 12 & 0b100 != 0 {
+                expect(&ops, 3, "rd, csr, zimm")?;
+                let zimm = imm(ops[2], 0, 31, "zimm")?;
+                rd(ops[0])? | csr(ops[1])? << 20 | (zimm as u32) << 15
+            } else {
+                expect(&ops, 3, "rd, csr, rs1")?;
+                rd(ops[0])? | csr(ops[1])? << 20 | rs1(ops[2])?
+            }
+        }
+        opcode::LOAD => {
+            expect(&ops, 2, "rd, offset(rs1)")?;
+            let (offset, base) = mem(ops[1])?;
+            rd(ops[0])? | base | imm_i(offset)
+        }
+        opcode::JALR => match ops.len() {
+            1 => rd("ra")? | rs1(ops[0])?,
+            2 => {
+                let (offset, base) = mem(ops[1])?;
+                rd(ops[0])? | base | imm_i(offset)
+            }
+            _ => {
+                expect(&ops, 3, "rd, rs1, offset")?;
+                rd(ops[0])? | rs1(ops[1])? | imm_i(imm(ops[2], -2048, 2047, "offset")?)
+            }
+        },
+        opcode::STORE => {
+            expect(&ops, 2, "rs2, offset(rs1)")?;
+            let (offset, base) = mem(ops[1])?;
+            rs2(ops[0])? | base | imm_s(offset)
+        }
+        opcode::BRANCH => {
+            expect(&ops, 3, "rs1, rs2, offset")?;
+            let offset = even(imm(ops[2], -4096, 4094, "offset")?)?;
+            rs1(ops[0])? | rs2(ops[1])? | imm_b(offset)
+        }
+        opcode::LUI | opcode::AUIPC => {
+            expect(&ops, 2, "rd, imm")?;
+            let value = imm(ops[1], -0x8_0000, 0xf_ffff, "imm")?;
+            rd(ops[0])? | (value as u32) << 12
+        }
+        opcode::JAL => {
+            let (link, target) = match ops.len() {
+                1 => ("ra", ops[0]),
+                _ => {
+                    expect(&ops, 2, "rd, offset")?;
+                    (ops[0], ops[1])
+                }
+            };
+            let offset = even(imm(target, -0x10_0000, 0xf_fffe, "offset")?)?;
+            rd(link)? | imm_j(offset)
+        }
+        // shifts have funct6/funct7 bits fixed
+        op @ (opcode::OP_IMM | opcode::OP_IMM_32) if def.mask >> 25 != 0 => {
+            expect(&ops, 3, "rd, rs1, shamt")?;
+            let max = if op == opcode::OP_IMM { 63 } else { 31 };
+            let shamt = imm(ops[2], 0, max, "shamt")?;
+            rd(ops[0])? | rs1(ops[1])? | (shamt as u32) << 20
+        }
+        opcode::OP_IMM | opcode::OP_IMM_32 => {
+            expect(&ops, 3, "rd, rs1, imm")?;
+            rd(ops[0])? | rs1(ops[1])? | imm_i(imm(ops[2], -2048, 2047, "imm")?)
+        }
+        opcode::AMO if def.name.starts_with("lr.") => {
+            expect(&ops, 2, "rd, (rs1)")?;
+            rd(ops[0])? | mem_reg(ops[1])?
+        }
+        opcode::AMO => {
+            expect(&ops, 3, "rd, rs2, (rs1)")?;
+            rd(ops[0])? | rs2(ops[1])? | mem_reg(ops[2])?
+        }
+        _ => {
+            expect(&ops, 3, "rd, rs1, rs2")?;
+            rd(ops[0])? | rs1(ops[1])? | rs2(ops[2])?
+        }
+    };
+    Ok(def.value | fields | aqrl << 25)
+}
+
+fn expect(ops: &[&str], count: usize, syntax: &str) -> Result<(), String> {
+    if ops.len() == count {
+        Ok(())
+    } else if count == 0 {
+        Err("expected no operands".to_string())
+    } else {
+        Err(format!("expected {count} operands: {syntax}"))
+    }
+}
+
+fn reg(text: &str) -> Result<u32, String> {
+    parse_reg(text)
+        .map(|reg| reg as u32)
+        .ok_or_else(|| format!("invalid register '{text}'"))
+}
+
+fn rd(text: &str) -> Result<u32, String> {
+    Ok(reg(text)? << 7)
+}
+
+fn rs1(text: &str) -> Result<u32, String> {
+    Ok(reg(text)? << 15)
+}
+
+fn rs2(text: &str) -> Result<u32, String> {
+    Ok(reg(text)? << 20)
+}
+
+fn imm(text: &str, min: i64, max: i64, what: &str) -> Result<i64, String> {
+    let value = parse_int(text).ok_or_else(|| format!("invalid {what} '{text}'"))?;
+    if value < min || value > max {
+        return Err(format!("{what} {value} out of range [{min}, {max}]"));
+    }
+    Ok(value)
+}
+
+fn even(offset: i64) -> Result<i64, String> {
+    if offset % 2 != 0 {
+        return Err(format!("offset {offset} is not a multiple of 2"));
+    }
+    Ok(offset)
+}
+
+/// Memory operand "offset(rs1)": (offset, rs1 field)
+fn mem(text: &str) -> Result<(i64, u32), String> {
+    let (offset, base) = text
+        .strip_suffix(')')
+        .and_then(|text| text.split_once('('))
+        .ok_or_else(|| format!("expected offset(rs1), got '{text}'"))?;
+    let offset = match offset.trim() {
+        "" => 0,
+        offset => imm(offset, -2048, 2047, "offset")?,
+    };
+    Ok((offset, rs1(base.trim())?))
+}
+
+/// Memory operand "(rs1)" of atomics: rs1 field
+fn mem_reg(text: &str) -> Result<u32, String> {
+    match mem(text)? {
+        (0, base) => Ok(base),
+        _ => Err("atomics take no offset: (rs1)".to_string()),
+    }
+}
+
+fn csr(text: &str) -> Result<u32, String> {
+    if let Some((addr, _)) = CSR_NAMES.iter().find(|(_, name)| *name == text) {
+        return Ok(*addr);
+    }
+    Ok(imm(text, 0, 0xfff, "CSR")? as u32)
+}
+
+/// Fence predecessor or successor set, e.g., "rw"
+fn fence_set(text: &str) -> Result<u32, String> {
+    text.chars().try_fold(0, |set, c| match c {
+        'i' => Ok(set | 0b1000),
+        'o' => Ok(set | 0b0100),
+        'r' => Ok(set | 0b0010),
+        'w' => Ok(set | 0b0001),
+        _ => Err(format!("invalid fence set '{text}'")),
+    })
+}
+
+fn imm_i(imm: i64) -> u32 {
+    (imm as u32 & 0xfff) << 20
+}
+
+fn imm_s(imm: i64) -> u32 {
+    let imm = imm as u32;
+    (imm >> 5 & 0x7f) << 25 | (imm & 0x1f) << 7
+}
+
+fn imm_b(imm: i64) -> u32 {
+    let imm = imm as u32;
+    (imm >> 12 & 1) << 31 | (imm >> 5 & 0x3f) << 25 | (imm >> 1 & 0xf) << 8 | (imm >> 11 & 1) << 7
+}
+
+fn imm_j(imm: i64) -> u32 {
+    let imm = imm as u32;
+    (imm >> 20 & 1) << 31
+        | (imm >> 1 & 0x3ff) << 21
+        | (imm >> 11 & 1) << 20
+        | (imm >> 12 & 0xff) << 12
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{instr_format::decode_fields, isa::lookup};
+
+    /// Assemble, then decode the name and the immediate back
+    fn round_trip(text: &str, name: &str, imm: Option<i64>) {
+        let instr = assemble(text).unwrap();
+        assert_eq!(lookup(instr).map(|def| def.name), Some(name), "{text}");
+        assert_eq!(decode_fields(instr).imm, imm, "{text}");
+    }
+
+    #[test]
+    fn one_instruction_per_format() {
+        round_trip("add a0, a1, a2", "add", None);
+        round_trip("addi a0, a0, -1", "addi", Some(-1));
+        round_trip("sd ra, -8(sp)", "sd", Some(-8));
+        round_trip("beq t0, t1, -8", "beq", Some(-8));
+        round_trip("lui a0, 0x12345", "lui", Some(0x1234_5000));
+        round_trip("jal ra, 2048", "jal", Some(2048));
+        round_trip("amoadd.w.aqrl a0, a1, (a2)", "amoadd.w", None);
+    }
+
+    #[test]
+    fn encodings() {
+        assert_eq!(assemble("addi a0, a0, 1"), Ok(0x0015_0513));
+        assert_eq!(assemble("lui a0, 0x12345"), Ok(0x1234_5537));
+        assert_eq!(assemble("jal ra, 8"), Ok(0x0080_00ef));
+        assert_eq!(assemble("jalr ra, 4(a0)"), Ok(0x0045_00e7));
+        assert_eq!(assemble("beq a0, a1, 8"), Ok(0x00b5_0463));
+        assert_eq!(assemble("ecall"), Ok(0x0000_0073));
+        // csrr t0, mhartid of uart_hello_world.bin
+        assert_eq!(assemble("csrrs t0, mhartid, zero"), Ok(0xf140_22f3));
+    }
+
+    #[test]
+    fn branch_and_jump_extremes() {
+        round_trip("beq a0, a1, -4096", "beq", Some(-4096));
+        round_trip("bne a0, a1, 4094", "bne", Some(4094));
+        round_trip("jal zero, -0x100000", "jal", Some(-0x10_0000));
+        round_trip("jal zero, 0xffffe", "jal", Some(0xf_fffe));
+        assert!(assemble("beq a0, a1, 4096").is_err());
+        assert!(assemble("beq a0, a1, -4098").is_err());
+        assert!(assemble("jal zero, 0x100000").is_err());
+        assert!(assemble("jal zero, 3").is_err());
+    }
+
+    #[test]
+    fn errors() {
+        assert!(assemble("addi a0, a0, 2048").is_err());
+        assert!(assemble("addi a0, a0").is_err());
+        assert!(assemble("add a0, a1, x32").is_err());
+        assert!(assemble("add.aq a0, a1, a2").is_err());
+        assert!(assemble("foo a0").is_err());
+    }
+}
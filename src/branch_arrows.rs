@@ -0,0 +1,179 @@
+//! Arrows from branch and jump instructions to their targets drawn in
+//! a column of the instruction list. Every row draws its own part of the arrows.
+
+use egui::{pos2, Color32, Stroke};
+
+use crate::instr_format::{decode_fields, instr_format, InstrFormat};
+
+/// Arrows which don't fit into the lanes are not drawn
+const MAX_LANES: usize = 8;
+const LANE_WIDTH: f32 = 6.0;
+
+const CONDITIONAL_COLOR: Color32 = Color32::from_rgb(90, 150, 230);
+const UNCONDITIONAL_COLOR: Color32 = Color32::from_rgb(80, 180, 90);
+const NOT_TAKEN_COLOR: Color32 = Color32::GRAY;
+
+pub struct Branch {
+    pub from_row: usize,
+    pub to_row: usize,
+    pub conditional: bool,
+    /// The branch is the current instruction
+    pub is_current: bool,
+    /// Outcome of the branch if it was the previous instruction
+    pub taken: Option<bool>,
+}
+
+pub struct Arrow {
+    branch: Branch,
+    lane: usize,
+}
+
+impl Arrow {
+    fn rows(&self) -> (usize, usize) {
+        let b = &self.branch;
+        (b.from_row.min(b.to_row), b.from_row.max(b.to_row))
+    }
+}
+
+/// Returns the target address of a branch or jump and whether the branch is conditional.
+/// Register jumps (jalr) have no static target.
+pub fn branch_target(instr: u32, addr: u64) -> Option<(u64, bool)> {
+    let conditional = match instr_format(instr) {
+        InstrFormat::B => true,
+        InstrFormat::J => false,
+        _ => return None,
+    };
+    let offset = decode_fields(instr).imm?;
+    Some((addr.wrapping_add(offset as u64), conditional))
+}
+
+/// Width of the column needed to draw all lanes
+pub fn column_width() -> f32 {
+    LANE_WIDTH * (MAX_LANES as f32 + 1.0)
+}
+
+/// Assign lanes to the branches so that overlapping arrows don't share a lane.
+/// Shorter arrows get lanes closer to the instructions.
+pub fn layout_arrows(mut branches: Vec<Branch>) -> Vec<Arrow> {
+    branches.sort_by_key(|b| b.from_row.abs_diff(b.to_row));
+    let mut arrows: Vec<Arrow> = Vec::new();
+    for branch in branches {
+        let (lo, hi) = (
+            branch.from_row.min(branch.to_row),
+            branch.from_row.max(branch.to_row),
+        );
+        let free_lane = (0..MAX_LANES).find(|lane| {
+            !arrows.iter().any(|a| {
+                let (a_lo, a_hi) = a.rows();
+                a.lane == *lane && a_lo <= hi && lo <= a_hi
+            })
+        });
+        if let Some(lane) = free_lane {
+            arrows.push(Arrow { branch, lane });
+        }
+    }
+    arrows
+}
+
+/// Draw parts of the arrows crossing the row
+pub fn draw_arrows(ui: &mut egui::Ui, row: usize, arrows: &[Arrow]) {
+    let half_spacing = 0.5 * ui.spacing().item_spacing.y;
+    let rect = ui.max_rect();
+    let (top, bottom, cy) = (
+        rect.top() - half_spacing,
+        rect.bottom() + half_spacing,
+        rect.center().y,
+    );
+    let right = rect.right() - 2.0;
+    let painter = ui.painter();
+    for arrow in arrows {
+        let b = &arrow.branch;
+        let (lo, hi) = arrow.rows();
+        if row < lo || row > hi {
+            continue;
+        }
+        let color = match b.taken {
+            Some(false) => NOT_TAKEN_COLOR,
+            _ if b.conditional => CONDITIONAL_COLOR,
+            _ => UNCONDITIONAL_COLOR,
+        };
+        let width = if b.is_current || b.taken.is_some() {
+            2.5
+        } else {
+            1.0
+        };
+        let stroke = Stroke::new(width, color);
+        let x = right - (arrow.lane as f32 + 1.0) * LANE_WIDTH;
+
+        if lo == hi {
+            // jump to itself, e.g., "j ." loop
+            let (y1, y2) = (cy - 3.0, cy + 3.0);
+            painter.line_segment([pos2(right, y1), pos2(x, y1)], stroke);
+            painter.line_segment([pos2(x, y1), pos2(x, y2)], stroke);
+            painter.line_segment([pos2(x, y2), pos2(right, y2)], stroke);
+            draw_arrow_head(painter, pos2(right, y2), stroke);
+            continue;
+        }
+        if row == b.from_row || row == b.to_row {
+            painter.line_segment([pos2(x, cy), pos2(right, cy)], stroke);
+            // vertical half towards the other end
+            let y = if row == lo { bottom } else { top };
+            painter.line_segment([pos2(x, cy), pos2(x, y)], stroke);
+            if row == b.to_row {
+                draw_arrow_head(painter, pos2(right, cy), stroke);
+            }
+        } else {
+            painter.line_segment([pos2(x, top), pos2(x, bottom)], stroke);
+        }
+    }
+}
+
+/// Arrow head pointing right with the tip at the point
+fn draw_arrow_head(painter: &egui::Painter, tip: egui::Pos2, stroke: Stroke) {
+    let size = 3.0;
+    painter.line_segment([tip, pos2(tip.x - size, tip.y - size)], stroke);
+    painter.line_segment([tip, pos2(tip.x - size, tip.y + size)], stroke);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn branch(from_row: usize, to_row: usize) -> Branch {
+        Branch {
+            from_row,
+            to_row,
+            conditional: false,
+            is_current: false,
+            taken: None,
+        }
+    }
+
+    #[test]
+    fn targets() {
+        // beq zero, zero, -8
+        assert_eq!(branch_target(0xfe00_0ce3, 0x1010), Some((0x1008, true)));
+        // j 16
+        assert_eq!(branch_target(0x0100_006f, 0x1000), Some((0x1010, false)));
+        // ret
+        assert_eq!(branch_target(0x0000_8067, 0x1000), None);
+        // nop
+        assert_eq!(branch_target(0x0000_0013, 0x1000), None);
+    }
+
+    #[test]
+    fn lanes() {
+        let arrows = layout_arrows(vec![
+            branch(0, 10),
+            branch(2, 4),
+            branch(5, 3),
+            branch(6, 8),
+        ]);
+        let lanes: Vec<_> = arrows.iter().map(|a| (a.branch.from_row, a.lane)).collect();
+        // shorter arrows first, overlapping ones in separate lanes
+        assert_eq!(lanes, [(2, 0), (5, 1), (6, 0), (0, 2)]);
+
+        let arrows = layout_arrows((0..=MAX_LANES).map(|row| branch(row, 20)).collect());
+        assert_eq!(arrows.len(), MAX_LANES);
+    }
+}
@@ -0,0 +1,293 @@
+use std::collections::{BTreeMap, HashSet};
+
+use crate::{elf::Symbol, number::parse_int, regs::parse_reg};
+
+pub struct Breakpoint {
+    pub addr: u64,
+    pub enabled: bool,
+    /// Stop only if the condition is true (e.g., "a0 == 5"), empty - always stop
+    pub condition: String,
+    /// Symbol and offset of the address to find it in a rebuilt image
+    location: Option<(String, u64)>,
+}
+
+/// Breakpoints set by the user. The simulator gets a copy of the enabled
+/// breakpoints every time they change.
+#[derive(Default)]
+pub struct Breakpoints {
+    list: Vec<Breakpoint>,
+    changed: bool,
+    /// Symbols of the loaded image by address
+    symbols: BTreeMap<u64, String>,
+}
+
+impl Breakpoints {
+    pub fn get(&self, addr: u64) -> Option<&Breakpoint> {
+        self.list.iter().find(|bp| bp.addr == addr)
+    }
+
+    pub fn add(&mut self, addr: u64) {
+        if self.get(addr).is_none() {
+            self.list.push(Breakpoint {
+                addr,
+                enabled: true,
+                condition: String::new(),
+                location: self.location(addr),
+            });
+            self.changed = true;
+        }
+    }
+
+    pub fn remove(&mut self, addr: u64) {
+        self.list.retain(|bp| bp.addr != addr);
+        self.changed = true;
+    }
+
+    pub fn remove_all(&mut self) {
+        self.list.clear();
+        self.changed = true;
+    }
+
+    pub fn toggle(&mut self, addr: u64) {
+        if self.get(addr).is_some() {
+            self.remove(addr);
+        } else {
+            self.add(addr);
+        }
+    }
+
+    pub fn set_enabled(&mut self, addr: u64, enabled: bool) {
+        if let Some(bp) = self.list.iter_mut().find(|bp| bp.addr == addr) {
+            bp.enabled = enabled;
+            self.changed = true;
+        }
+    }
+
+    pub fn set_condition(&mut self, addr: u64, condition: String) {
+        if let Some(bp) = self.list.iter_mut().find(|bp| bp.addr == addr) {
+            bp.condition = condition;
+            self.changed = true;
+        }
+    }
+
+    /// Symbols of the newly loaded image. Breakpoints inside symbols move to
+    /// the same symbol and offset, the others stay at their addresses.
+    pub fn set_symbols(&mut self, symbols: &[Symbol]) {
+        let old_list = std::mem::take(&mut self.list);
+        self.symbols = symbols
+            .iter()
+            .map(|symbol| (symbol.addr, symbol.name.clone()))
+            .collect();
+        let mut addrs = HashSet::new();
+        for mut bp in old_list {
+            let new_addr = bp.location.as_ref().and_then(|(name, offset)| {
+                let symbol = symbols.iter().find(|symbol| symbol.name == *name)?;
+                Some(symbol.addr.wrapping_add(*offset))
+            });
+            if let Some(addr) = new_addr.filter(|addr| *addr != bp.addr) {
+                bp.addr = addr;
+                self.changed = true;
+            }
+            // a symbol missing in this image is kept for the next one
+            if new_addr.is_some() || bp.location.is_none() {
+                bp.location = self.location(bp.addr);
+            }
+            // two breakpoints can move to the same address
+            if addrs.insert(bp.addr) {
+                self.list.push(bp);
+            } else {
+                self.changed = true;
+            }
+        }
+    }
+
+    /// Symbol containing the address (the nearest one before it) and the offset
+    fn location(&self, addr: u64) -> Option<(String, u64)> {
+        let (symbol_addr, name) = self.symbols.range(..=addr).next_back()?;
+        Some((name.clone(), addr - symbol_addr))
+    }
+
+    /// Returns breakpoints for the simulator if they changed since the last call
+    pub fn take_changed(&mut self) -> Option<Vec<SimBreakpoint>> {
+        if !self.changed {
+            return None;
+        }
+        self.changed = false;
+        Some(
+            self.list
+                .iter()
+                .filter(|bp| bp.enabled)
+                .map(|bp| SimBreakpoint {
+                    addr: bp.addr,
+                    // invalid conditions are reported in the UI, stop unconditionally
+                    condition: Condition::parse(&bp.condition).unwrap_or(None),
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Enabled breakpoint as checked by the simulator
+pub struct SimBreakpoint {
+    pub addr: u64,
+    pub condition: Option<Condition>,
+}
+
+#[derive(Clone, Copy)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// Comparison of a register with a value: "<reg> <op> <value>"
+pub struct Condition {
+    reg: usize,
+    op: CmpOp,
+    value: i64,
+}
+
+impl Condition {
+    /// Returns None for an empty condition
+    pub fn parse(text: &str) -> Result<Option<Condition>, String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Ok(None);
+        }
+        // two-character operators go first so "<=" isn't taken for "<"
+        const OPS: [(&str, CmpOp); 6] = [
+            ("==", CmpOp::Eq),
+            ("!=", CmpOp::Ne),
+            ("<=", CmpOp::Le),
+            (">=", CmpOp::Ge),
+            ("<", CmpOp::Lt),
+            (">", CmpOp::Gt),
+        ];
+        let (op_str, op) = OPS
+            .iter()
+            .find(|(op_str, _)| text.contains(op_str))
+            .ok_or("expected a comparison: ==, !=, <, <=, >, >=")?;
+        let (reg, value) = text.split_once(op_str).unwrap();
+        let reg = parse_reg(reg).ok_or_else(|| format!("unknown register: {}", reg.trim()))?;
+        let value = parse_int(value).ok_or_else(|| format!("invalid value: {}", value.trim()))?;
+        Ok(Some(Condition {
+            reg,
+            op: *op,
+            value,
+        }))
+    }
+
+    /// Evaluate the condition on the integer registers (signed comparison)
+    pub fn eval(&self, x: &[u64; 32]) -> bool {
+        let reg = x[self.reg] as i64;
+        match self.op {
+            CmpOp::Eq => reg == self.value,
+            CmpOp::Ne => reg != self.value,
+            CmpOp::Lt => reg < self.value,
+            CmpOp::Le => reg <= self.value,
+            CmpOp::Gt => reg > self.value,
+            CmpOp::Ge => reg >= self.value,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(condition: &str, reg: usize, value: u64) -> bool {
+        let mut x = [0; 32];
+        x[reg] = value;
+        Condition::parse(condition).unwrap().unwrap().eval(&x)
+    }
+
+    #[test]
+    fn conditions() {
+        assert!(Condition::parse("  ").unwrap().is_none());
+        assert!(eval("a0 == 5", 10, 5));
+        assert!(!eval("a0 != 5", 10, 5));
+        assert!(eval("x11 <= 0x10", 11, 0x10));
+        assert!(!eval("x11 < 0x10", 11, 0x10));
+        assert!(eval("t0 >= -1", 5, 0));
+        // signed comparison
+        assert!(eval("sp < 0", 2, u64::MAX));
+        assert!(eval("a0 == 0xffff_ffff_ffff_ffff", 10, u64::MAX));
+        assert!(eval("a1 > -0x10", 11, (-8i64) as u64));
+    }
+
+    #[test]
+    fn invalid_conditions() {
+        assert!(Condition::parse("a0").is_err());
+        assert!(Condition::parse("q0 == 1").is_err());
+        assert!(Condition::parse("a0 == one").is_err());
+    }
+
+    #[test]
+    fn changes_go_to_the_simulator() {
+        let mut breakpoints = Breakpoints::default();
+        assert!(breakpoints.take_changed().is_none());
+        breakpoints.add(0x8000_0000);
+        breakpoints.add(0x8000_0010);
+        breakpoints.set_condition(0x8000_0010, "a0 ==".to_string());
+        breakpoints.set_enabled(0x8000_0000, false);
+        let sim = breakpoints.take_changed().unwrap();
+        assert!(breakpoints.take_changed().is_none());
+        // disabled breakpoints are left out, invalid conditions stop always
+        assert_eq!(sim.len(), 1);
+        assert_eq!(sim[0].addr, 0x8000_0010);
+        assert!(sim[0].condition.is_none());
+        breakpoints.toggle(0x8000_0010);
+        assert!(breakpoints.get(0x8000_0010).is_none());
+        assert_eq!(breakpoints.take_changed().unwrap().len(), 0);
+    }
+
+    fn symbols(list: &[(&str, u64)]) -> Vec<Symbol> {
+        list.iter()
+            .map(|(name, addr)| Symbol {
+                name: name.to_string(),
+                addr: *addr,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn relocation() {
+        let mut breakpoints = Breakpoints::default();
+        breakpoints.set_symbols(&symbols(&[("main", 0x8000_0000), ("loop", 0x8000_0100)]));
+        breakpoints.add(0x8000_0008);
+        breakpoints.add(0x8000_0104);
+        breakpoints.take_changed();
+
+        // a rebuilt image moves main and drops loop
+        breakpoints.set_symbols(&symbols(&[("main", 0x8000_0040)]));
+        assert!(breakpoints.get(0x8000_0048).is_some());
+        assert!(breakpoints.get(0x8000_0104).is_some());
+        assert_eq!(breakpoints.take_changed().unwrap().len(), 2);
+
+        // loop comes back and the breakpoint follows it
+        breakpoints.set_symbols(&symbols(&[("main", 0x8000_0040), ("loop", 0x8000_0200)]));
+        assert!(breakpoints.get(0x8000_0204).is_some());
+        assert!(breakpoints.get(0x8000_0104).is_none());
+        assert!(breakpoints.take_changed().is_some());
+
+        // an unchanged image changes nothing
+        breakpoints.set_symbols(&symbols(&[("main", 0x8000_0040), ("loop", 0x8000_0200)]));
+        assert!(breakpoints.take_changed().is_none());
+    }
+
+    #[test]
+    fn relocation_to_the_same_address() {
+        let mut breakpoints = Breakpoints::default();
+        breakpoints.set_symbols(&symbols(&[("a", 0x1000), ("b", 0x2000)]));
+        breakpoints.add(0x1000);
+        breakpoints.add(0x2000);
+        breakpoints.take_changed();
+        breakpoints.set_symbols(&symbols(&[("a", 0x3000), ("b", 0x3000)]));
+        let sim = breakpoints.take_changed().unwrap();
+        assert_eq!(sim.len(), 1);
+        assert_eq!(sim[0].addr, 0x3000);
+    }
+}
@@ -6,6 +6,9 @@ pub struct Console {
     //font_size: usize,
     #[serde(skip)]
     buffer: String,
+    /// The last received character was CR, a following LF ends the same line
+    #[serde(skip)]
+    after_cr: bool,
 }
 
 impl Console {
@@ -15,7 +18,7 @@ impl Console {
 
     pub fn show(&mut self, ctx: &egui::Context, new_bytes: Option<String>) {
         if let Some(new_bytes) = new_bytes {
-            self.buffer.push_str(&new_bytes)
+            self.push_output(&new_bytes);
         }
         let mut open = self.open;
         egui::Window::new("Console")
@@ -23,6 +26,14 @@ impl Console {
             .resizable(true)
             .default_width(400.0)
             .show(ctx, |ui| {
+                // sending keys to the guest is blocked on a receive API in kompusim's UART
+                ui.add_enabled(
+                    false,
+                    egui::TextEdit::singleline(&mut "")
+                        .hint_text("keyboard input is not implemented yet")
+                        .desired_width(f32::INFINITY),
+                )
+                .on_disabled_hover_text("Needs a receive API in kompusim's UART");
                 egui::ScrollArea::vertical().show(ui, |ui| {
                     ui.add(
                         egui::TextEdit::multiline(&mut self.buffer.as_str())
@@ -36,4 +47,16 @@ impl Console {
             });
         self.open = open;
     }
+
+    /// Append output of the guest, CR, LF and CR LF all end a line
+    fn push_output(&mut self, text: &str) {
+        for c in text.chars() {
+            match c {
+                '\r' => self.buffer.push('\n'),
+                '\n' if self.after_cr => {}
+                c => self.buffer.push(c),
+            }
+            self.after_cr = c == '\r';
+        }
+    }
 }
@@ -0,0 +1,118 @@
+use crate::{
+    fdt::{self, FdtNode},
+    number::parse_addr,
+    sim::MachineConfig,
+};
+
+#[derive(serde::Deserialize, serde::Serialize, Default)]
+#[serde(default)]
+pub struct DeviceTree {
+    /// Is window open or not
+    window_open: bool,
+    #[serde(skip)]
+    dtb_addr_hex: String,
+    #[serde(skip)]
+    dtb_addr_error: Option<String>,
+    /// The DTB was moved (note) or doesn't fit into RAM (error) on the last reset
+    #[serde(skip)]
+    dtb_placement: Option<Result<String, String>>,
+    /// Machine configuration the decoded tree was generated for
+    #[serde(skip)]
+    config: Option<MachineConfig>,
+    #[serde(skip)]
+    tree: Option<Result<FdtNode, String>>,
+}
+
+impl DeviceTree {
+    pub fn open(&mut self) {
+        self.window_open = true;
+    }
+
+    /// Result of MachineConfig::place_dtb on the last reset
+    pub fn set_dtb_placement(&mut self, placement: Result<Option<String>, String>) {
+        self.dtb_placement = placement.transpose();
+    }
+
+    /// Returns the new DTB address if the user changed it
+    pub fn show(&mut self, ctx: &egui::Context, config: &MachineConfig) -> Option<Option<u64>> {
+        if self.config.as_ref() != Some(config) {
+            self.config = Some(*config);
+            self.dtb_addr_hex = match config.dtb_addr {
+                Some(addr) => format!("0x{addr:x}"),
+                None => String::new(),
+            };
+            self.dtb_addr_error = None;
+            self.tree = config.dtb_addr.map(|_| fdt::parse(&fdt::generate(config)));
+        }
+        let mut new_dtb_addr = None;
+        let mut open = self.window_open;
+        egui::Window::new("Device tree")
+            .open(&mut open)
+            .resizable(true)
+            .default_width(400.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("DTB address");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.dtb_addr_hex)
+                            .hint_text("no device tree")
+                            .desired_width(150.0),
+                    );
+                    if ui.button("Apply and reset").clicked() {
+                        self.dtb_addr_error = None;
+                        let addr = self.dtb_addr_hex.trim();
+                        if addr.is_empty() {
+                            new_dtb_addr = Some(None);
+                        } else if let Some(addr) = parse_addr(addr) {
+                            new_dtb_addr = Some(Some(addr));
+                        } else {
+                            self.dtb_addr_error = Some(format!("invalid address '{addr}'"));
+                        }
+                    }
+                });
+                if let Some(err) = &self.dtb_addr_error {
+                    ui.colored_label(ui.visuals().error_fg_color, err);
+                }
+                match &self.dtb_placement {
+                    Some(Ok(note)) => {
+                        ui.colored_label(ui.visuals().warn_fg_color, note);
+                    }
+                    Some(Err(err)) => {
+                        ui.colored_label(ui.visuals().error_fg_color, err);
+                    }
+                    None => {}
+                }
+                ui.label("The address is passed to the program in register a1");
+                ui.separator();
+                egui::ScrollArea::vertical().show(ui, |ui| match &self.tree {
+                    Some(Ok(root)) => show_node(ui, root, "/"),
+                    Some(Err(err)) => {
+                        ui.colored_label(ui.visuals().error_fg_color, err);
+                    }
+                    None => {
+                        ui.label("The machine has no device tree");
+                    }
+                });
+            });
+        self.window_open = open;
+        new_dtb_addr
+    }
+}
+
+fn show_node(ui: &mut egui::Ui, node: &FdtNode, name: &str) {
+    egui::CollapsingHeader::new(egui::RichText::new(name).monospace())
+        .default_open(true)
+        .show(ui, |ui| {
+            for prop in &node.props {
+                let value = prop.value_to_string();
+                if value.is_empty() {
+                    ui.monospace(format!("{};", prop.name));
+                } else {
+                    ui.monospace(format!("{} = {};", prop.name, value));
+                }
+            }
+            for child in &node.children {
+                show_node(ui, child, &child.name);
+            }
+        });
+}
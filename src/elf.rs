@@ -0,0 +1,386 @@
+//! Minimal ELF64 little-endian parser: loadable segments, sections and symbols
+
+const PT_LOAD: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_NOBITS: u32 = 8;
+pub const SHF_ALLOC: u64 = 0x2;
+pub const SHF_EXECINSTR: u64 = 0x4;
+pub const SHF_STRINGS: u64 = 0x20;
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+const EM_RISCV: u16 = 243;
+
+/// Loadable segment (PT_LOAD), memory beyond the file data is zero
+pub struct Segment {
+    pub addr: u64,
+    pub data: Vec<u8>,
+    pub mem_size: u64,
+}
+
+pub struct Section {
+    pub name: String,
+    pub addr: u64,
+    pub size: u64,
+    pub flags: u64,
+    /// Section occupies no space in the file (.bss)
+    pub nobits: bool,
+}
+
+pub struct Symbol {
+    pub name: String,
+    pub addr: u64,
+}
+
+pub struct Elf {
+    pub entry: u64,
+    pub segments: Vec<Segment>,
+    pub sections: Vec<Section>,
+    /// Function and object symbols
+    pub symbols: Vec<Symbol>,
+}
+
+pub fn is_elf(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"\x7fELF")
+}
+
+/// Bounds checked little-endian reads
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn slice(&self, off: u64, len: u64) -> Result<&'a [u8], String> {
+        off.checked_add(len)
+            .and_then(|end| self.bytes.get(off as usize..end as usize))
+            .ok_or_else(|| format!("truncated ELF: {len} bytes at offset 0x{off:x}"))
+    }
+
+    fn u8(&self, off: u64) -> Result<u8, String> {
+        Ok(self.slice(off, 1)?[0])
+    }
+
+    fn u16(&self, off: u64) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.slice(off, 2)?.try_into().unwrap()))
+    }
+
+    fn u32(&self, off: u64) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.slice(off, 4)?.try_into().unwrap()))
+    }
+
+    fn u64(&self, off: u64) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.slice(off, 8)?.try_into().unwrap()))
+    }
+
+    /// NUL-terminated string
+    fn str(&self, off: u64) -> Result<String, String> {
+        let tail = self
+            .bytes
+            .get(off as usize..)
+            .ok_or_else(|| format!("truncated ELF: string at offset 0x{off:x}"))?;
+        let len = tail.iter().position(|b| *b == 0).unwrap_or(tail.len());
+        Ok(String::from_utf8_lossy(&tail[..len]).into_owned())
+    }
+}
+
+/// Sum of offsets or sizes from the file, which can overflow in a malformed one
+fn add(a: u64, b: u64) -> Result<u64, String> {
+    a.checked_add(b)
+        .ok_or_else(|| format!("malformed ELF: 0x{a:x} + 0x{b:x} overflows"))
+}
+
+/// Offset of the entry `i` of a table of entries of `entry_size` bytes
+fn table_entry(table: u64, i: u64, entry_size: u64) -> Result<u64, String> {
+    add(
+        table,
+        i.checked_mul(entry_size)
+            .ok_or("malformed ELF: table too large")?,
+    )
+}
+
+pub fn parse(bytes: &[u8]) -> Result<Elf, String> {
+    let r = Reader { bytes };
+    if !is_elf(bytes) {
+        return Err("not an ELF file".to_string());
+    }
+    if r.u8(4)? != 2 || r.u8(5)? != 1 {
+        return Err("only 64-bit little-endian ELF files are supported".to_string());
+    }
+    let machine = r.u16(18)?;
+    if machine != EM_RISCV {
+        return Err(format!("not a RISC-V ELF file (machine {machine})"));
+    }
+    let entry = r.u64(24)?;
+    let (phoff, shoff) = (r.u64(32)?, r.u64(40)?);
+    let (phentsize, phnum) = (r.u16(54)? as u64, r.u16(56)? as u64);
+    let (shentsize, shnum, shstrndx) = (r.u16(58)? as u64, r.u16(60)? as u64, r.u16(62)? as u64);
+
+    let mut segments = Vec::new();
+    for i in 0..phnum {
+        let ph = table_entry(phoff, i, phentsize)?;
+        // Elf64_Phdr is 56 bytes, offsets inside it can't overflow once it's in the file
+        r.slice(ph, 56)?;
+        if r.u32(ph)? != PT_LOAD {
+            continue;
+        }
+        let (offset, vaddr) = (r.u64(ph + 8)?, r.u64(ph + 16)?);
+        let (file_size, mem_size) = (r.u64(ph + 32)?, r.u64(ph + 40)?);
+        if mem_size == 0 {
+            continue;
+        }
+        segments.push(Segment {
+            addr: vaddr,
+            data: r.slice(offset, file_size)?.to_vec(),
+            mem_size: mem_size.max(file_size),
+        });
+    }
+    if segments.is_empty() {
+        return Err("ELF file has no loadable segments".to_string());
+    }
+
+    // (name offset, type, flags, addr, offset, size, link)
+    let mut headers = Vec::new();
+    for i in 0..shnum {
+        let sh = table_entry(shoff, i, shentsize)?;
+        // Elf64_Shdr is 64 bytes
+        r.slice(sh, 64)?;
+        headers.push((
+            r.u32(sh)? as u64,
+            r.u32(sh + 4)?,
+            r.u64(sh + 8)?,
+            r.u64(sh + 16)?,
+            r.u64(sh + 24)?,
+            r.u64(sh + 32)?,
+            r.u32(sh + 40)? as usize,
+        ));
+    }
+    let shstrtab_off = headers.get(shstrndx as usize).map(|h| h.4);
+    let mut sections = Vec::new();
+    let mut symbols = Vec::new();
+    for (name, sh_type, flags, addr, offset, size, link) in &headers {
+        if *sh_type == SHT_SYMTAB {
+            let strtab_off = match headers.get(*link) {
+                Some(strtab) => strtab.4,
+                None => continue,
+            };
+            // Elf64_Sym is 24 bytes
+            for sym in (*offset..add(*offset, *size)?).step_by(24) {
+                r.slice(sym, 24)?;
+                let info = r.u8(sym + 4)?;
+                let sym_type = info & 0xf;
+                let addr = r.u64(sym + 8)?;
+                if sym_type != STT_FUNC && sym_type != STT_OBJECT && sym_type != 0 {
+                    continue;
+                }
+                let name = r.str(add(strtab_off, r.u32(sym)? as u64)?)?;
+                // skip mapping symbols ($x, $d) and local assembler labels
+                if addr == 0 || name.is_empty() || name.starts_with('$') || name.starts_with(".L") {
+                    continue;
+                }
+                symbols.push(Symbol { name, addr });
+            }
+        }
+        if flags & SHF_ALLOC != 0 && *size > 0 {
+            let name = match shstrtab_off {
+                Some(off) => r.str(add(off, *name)?)?,
+                None => String::new(),
+            };
+            sections.push(Section {
+                name,
+                addr: *addr,
+                size: *size,
+                flags: *flags,
+                nobits: *sh_type == SHT_NOBITS,
+            });
+        }
+    }
+    Ok(Elf {
+        entry,
+        segments,
+        sections,
+        symbols,
+    })
+}
+
+impl Elf {
+    /// Loadable segments as one contiguous image: (address, bytes).
+    /// Gaps between segments are filled with zeros.
+    pub fn flat_image(&self) -> Result<(u64, Vec<u8>), String> {
+        /// Protection against sparse images, e.g., code in ROM and data in RAM
+        const MAX_IMAGE_SIZE: u64 = 256 * 1024 * 1024;
+        let start = self.segments.iter().map(|s| s.addr).min().unwrap_or(0);
+        let mut end = 0;
+        for segment in &self.segments {
+            end = end.max(add(segment.addr, segment.mem_size)?);
+        }
+        if end - start > MAX_IMAGE_SIZE {
+            return Err(format!(
+                "loadable segments span 0x{:x} bytes from 0x{start:x}",
+                end - start
+            ));
+        }
+        let mut image = vec![0; (end - start) as usize];
+        for segment in &self.segments {
+            let off = (segment.addr - start) as usize;
+            image[off..off + segment.data.len()].copy_from_slice(&segment.data);
+        }
+        Ok((start, image))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: u64 = 0x8000_0000;
+    const PHOFF: usize = 64;
+    const SHOFF: usize = 0x200;
+
+    fn put(elf: &mut [u8], off: usize, bytes: &[u8]) {
+        elf[off..off + bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// .text with 8 bytes of code, .data with 4 bytes and 4 bytes of bss,
+    /// symbols _start, msg and the mapping symbol $x
+    fn build_elf() -> Vec<u8> {
+        let mut elf = vec![0; SHOFF + 6 * 64];
+        put(&mut elf, 0, b"\x7fELF\x02\x01\x01");
+        put(&mut elf, 18, &EM_RISCV.to_le_bytes());
+        put(&mut elf, 24, &BASE.to_le_bytes());
+        put(&mut elf, 32, &(PHOFF as u64).to_le_bytes());
+        put(&mut elf, 40, &(SHOFF as u64).to_le_bytes());
+        put(&mut elf, 54, &56u16.to_le_bytes());
+        put(&mut elf, 56, &2u16.to_le_bytes());
+        put(&mut elf, 58, &64u16.to_le_bytes());
+        put(&mut elf, 60, &6u16.to_le_bytes());
+        put(&mut elf, 62, &5u16.to_le_bytes());
+
+        // (file offset, address, file size, memory size)
+        let segments = [(0x100, BASE, 8, 8), (0x108, BASE + 0x10, 4, 8)];
+        for (i, (offset, addr, file_size, mem_size)) in segments.into_iter().enumerate() {
+            let ph = PHOFF + i * 56;
+            put(&mut elf, ph, &PT_LOAD.to_le_bytes());
+            put(&mut elf, ph + 8, &(offset as u64).to_le_bytes());
+            put(&mut elf, ph + 16, &addr.to_le_bytes());
+            put(&mut elf, ph + 32, &(file_size as u64).to_le_bytes());
+            put(&mut elf, ph + 40, &(mem_size as u64).to_le_bytes());
+        }
+        put(
+            &mut elf,
+            0x100,
+            &[0x13, 0x05, 0x15, 0x00, 0x67, 0x80, 0x00, 0x00],
+        );
+        put(&mut elf, 0x108, b"hi!\0");
+
+        let strtab = b"\0_start\0msg\0$x\0";
+        put(&mut elf, 0x110, strtab);
+        let shstrtab = b"\0.text\0.data\0.symtab\0.strtab\0.shstrtab\0";
+        put(&mut elf, 0x130, shstrtab);
+        // (name, info, address), the first symbol is null
+        let symbols = [
+            (1u32, STT_FUNC, BASE),
+            (8, STT_OBJECT, BASE + 0x10),
+            (12, 0, BASE),
+        ];
+        for (i, (name, info, addr)) in symbols.into_iter().enumerate() {
+            let sym = 0x160 + (i + 1) * 24;
+            put(&mut elf, sym, &name.to_le_bytes());
+            put(&mut elf, sym + 4, &[info]);
+            put(&mut elf, sym + 8, &addr.to_le_bytes());
+        }
+
+        // (name, type, flags, address, offset, size, link), the first section is null
+        let sections = [
+            (
+                1u32,
+                1u32,
+                SHF_ALLOC | SHF_EXECINSTR,
+                BASE,
+                0x100u64,
+                8u64,
+                0u32,
+            ),
+            (7, 1, SHF_ALLOC, BASE + 0x10, 0x108, 8, 0),
+            (13, SHT_SYMTAB, 0, 0, 0x160, 4 * 24, 4),
+            (21, 3, 0, 0, 0x110, strtab.len() as u64, 0),
+            (29, 3, 0, 0, 0x130, shstrtab.len() as u64, 0),
+        ];
+        for (i, (name, sh_type, flags, addr, offset, size, link)) in
+            sections.into_iter().enumerate()
+        {
+            let sh = SHOFF + (i + 1) * 64;
+            put(&mut elf, sh, &name.to_le_bytes());
+            put(&mut elf, sh + 4, &sh_type.to_le_bytes());
+            put(&mut elf, sh + 8, &flags.to_le_bytes());
+            put(&mut elf, sh + 16, &addr.to_le_bytes());
+            put(&mut elf, sh + 24, &offset.to_le_bytes());
+            put(&mut elf, sh + 32, &size.to_le_bytes());
+            put(&mut elf, sh + 40, &link.to_le_bytes());
+        }
+        elf
+    }
+
+    #[test]
+    fn parses_segments_sections_and_symbols() {
+        let elf = parse(&build_elf()).unwrap();
+        assert_eq!(elf.entry, BASE);
+        assert_eq!(elf.segments.len(), 2);
+        assert_eq!(elf.segments[1].mem_size, 8);
+        let sections: Vec<(&str, u64, u64)> = elf
+            .sections
+            .iter()
+            .map(|s| (s.name.as_str(), s.addr, s.size))
+            .collect();
+        assert_eq!(
+            sections,
+            vec![(".text", BASE, 8), (".data", BASE + 0x10, 8)]
+        );
+        let symbols: Vec<(&str, u64)> = elf
+            .symbols
+            .iter()
+            .map(|s| (s.name.as_str(), s.addr))
+            .collect();
+        assert_eq!(symbols, vec![("_start", BASE), ("msg", BASE + 0x10)]);
+    }
+
+    #[test]
+    fn flat_image_fills_gaps_and_bss() {
+        let (addr, image) = parse(&build_elf()).unwrap().flat_image().unwrap();
+        assert_eq!(addr, BASE);
+        assert_eq!(image.len(), 0x18);
+        assert_eq!(
+            &image[..8],
+            &[0x13, 0x05, 0x15, 0x00, 0x67, 0x80, 0x00, 0x00]
+        );
+        assert_eq!(&image[8..0x10], &[0; 8]);
+        assert_eq!(&image[0x10..], b"hi!\0\0\0\0\0");
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        let elf = build_elf();
+        assert!(parse(&elf[..0x100]).is_err());
+        assert!(parse(b"\x7fELF").is_err());
+
+        let mut other_machine = elf.clone();
+        put(&mut other_machine, 18, &62u16.to_le_bytes());
+        assert!(parse(&other_machine).is_err());
+
+        let mut huge_phoff = elf.clone();
+        put(&mut huge_phoff, 32, &(u64::MAX - 8).to_le_bytes());
+        assert!(parse(&huge_phoff).is_err());
+
+        // symbol table size overflowing its offset
+        let mut huge_symtab = elf.clone();
+        put(
+            &mut huge_symtab,
+            SHOFF + 3 * 64 + 32,
+            &u64::MAX.to_le_bytes(),
+        );
+        assert!(parse(&huge_symtab).is_err());
+
+        // a segment ending beyond the address space
+        let mut huge_segment = elf;
+        put(&mut huge_segment, PHOFF + 56 + 40, &u64::MAX.to_le_bytes());
+        assert!(parse(&huge_segment).unwrap().flat_image().is_err());
+    }
+}
@@ -0,0 +1,337 @@
+//! Flattened device tree (FDT) generation and decoding.
+//! Format: https://devicetree-specification.readthedocs.io/en/stable/flattened-format.html
+
+use crate::sim::MachineConfig;
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_VERSION: u32 = 17;
+const FDT_LAST_COMP_VERSION: u32 = 16;
+const FDT_HEADER_SIZE: usize = 40;
+/// Header is followed by a single (terminating) memory reservation entry
+const FDT_RSVMAP_SIZE: usize = 16;
+
+const FDT_BEGIN_NODE: u32 = 0x1;
+const FDT_END_NODE: u32 = 0x2;
+const FDT_PROP: u32 = 0x3;
+const FDT_NOP: u32 = 0x4;
+const FDT_END: u32 = 0x9;
+
+/// ISA string advertised in the CPU node
+const CPU_ISA: &str = "rv64i";
+const TIMEBASE_FREQUENCY: u32 = 10_000_000;
+
+/// Generate the device tree blob describing the simulated machine
+pub fn generate(config: &MachineConfig) -> Vec<u8> {
+    let uart_node = format!("serial@{:x}", config.uart_base);
+    let mut fdt = FdtWriter::new();
+    fdt.begin_node("");
+    fdt.prop_u32("#address-cells", 2);
+    fdt.prop_u32("#size-cells", 2);
+    fdt.prop_str("compatible", "kompusim");
+    fdt.prop_str("model", "Kompusim RISC-V simulator");
+
+    fdt.begin_node("chosen");
+    fdt.prop_str("stdout-path", &format!("/soc/{uart_node}"));
+    fdt.end_node();
+
+    fdt.begin_node("cpus");
+    fdt.prop_u32("#address-cells", 1);
+    fdt.prop_u32("#size-cells", 0);
+    fdt.prop_u32("timebase-frequency", TIMEBASE_FREQUENCY);
+    fdt.begin_node("cpu@0");
+    fdt.prop_str("device_type", "cpu");
+    fdt.prop_u32("reg", 0);
+    fdt.prop_str("status", "okay");
+    fdt.prop_str("compatible", "riscv");
+    fdt.prop_str("riscv,isa", CPU_ISA);
+    fdt.end_node();
+    fdt.end_node();
+
+    fdt.begin_node(&format!("memory@{:x}", config.ram_base));
+    fdt.prop_str("device_type", "memory");
+    fdt.prop_u64_pair("reg", config.ram_base, config.ram_size);
+    fdt.end_node();
+
+    fdt.begin_node("soc");
+    fdt.prop_u32("#address-cells", 2);
+    fdt.prop_u32("#size-cells", 2);
+    fdt.prop_str("compatible", "simple-bus");
+    fdt.prop("ranges", &[]);
+    fdt.begin_node(&uart_node);
+    fdt.prop_str("compatible", "sifive,uart0");
+    fdt.prop_u64_pair("reg", config.uart_base, 0x20);
+    fdt.end_node();
+    fdt.end_node();
+
+    fdt.end_node();
+    fdt.finish()
+}
+
+struct FdtWriter {
+    structure: Vec<u8>,
+    strings: Vec<u8>,
+}
+
+impl FdtWriter {
+    fn new() -> FdtWriter {
+        FdtWriter {
+            structure: Vec::new(),
+            strings: Vec::new(),
+        }
+    }
+
+    fn token(&mut self, token: u32) {
+        self.structure.extend_from_slice(&token.to_be_bytes());
+    }
+
+    /// Append bytes to the structure block padding them to 4 bytes
+    fn padded(&mut self, bytes: &[u8]) {
+        self.structure.extend_from_slice(bytes);
+        while !self.structure.len().is_multiple_of(4) {
+            self.structure.push(0);
+        }
+    }
+
+    /// Returns offset of the name in the strings block, reusing existing names
+    fn string_offset(&mut self, name: &str) -> u32 {
+        let mut off = 0;
+        for s in self.strings.split(|b| *b == 0) {
+            if s == name.as_bytes() && off < self.strings.len() {
+                return off as u32;
+            }
+            off += s.len() + 1;
+        }
+        let off = self.strings.len();
+        self.strings.extend_from_slice(name.as_bytes());
+        self.strings.push(0);
+        off as u32
+    }
+
+    fn begin_node(&mut self, name: &str) {
+        self.token(FDT_BEGIN_NODE);
+        let mut name = name.as_bytes().to_vec();
+        name.push(0);
+        self.padded(&name);
+    }
+
+    fn end_node(&mut self) {
+        self.token(FDT_END_NODE);
+    }
+
+    fn prop(&mut self, name: &str, value: &[u8]) {
+        let name_off = self.string_offset(name);
+        self.token(FDT_PROP);
+        self.structure
+            .extend_from_slice(&(value.len() as u32).to_be_bytes());
+        self.structure.extend_from_slice(&name_off.to_be_bytes());
+        self.padded(value);
+    }
+
+    fn prop_u32(&mut self, name: &str, value: u32) {
+        self.prop(name, &value.to_be_bytes());
+    }
+
+    /// Property of two 64-bit values (e.g., address and size in "reg")
+    fn prop_u64_pair(&mut self, name: &str, v1: u64, v2: u64) {
+        let mut value = v1.to_be_bytes().to_vec();
+        value.extend_from_slice(&v2.to_be_bytes());
+        self.prop(name, &value);
+    }
+
+    fn prop_str(&mut self, name: &str, value: &str) {
+        let mut value = value.as_bytes().to_vec();
+        value.push(0);
+        self.prop(name, &value);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.token(FDT_END);
+        let off_mem_rsvmap = FDT_HEADER_SIZE;
+        let off_dt_struct = off_mem_rsvmap + FDT_RSVMAP_SIZE;
+        let off_dt_strings = off_dt_struct + self.structure.len();
+        let total_size = off_dt_strings + self.strings.len();
+
+        let mut blob = Vec::with_capacity(total_size);
+        for field in [
+            FDT_MAGIC,
+            total_size as u32,
+            off_dt_struct as u32,
+            off_dt_strings as u32,
+            off_mem_rsvmap as u32,
+            FDT_VERSION,
+            FDT_LAST_COMP_VERSION,
+            0, // boot_cpuid_phys
+            self.strings.len() as u32,
+            self.structure.len() as u32,
+        ] {
+            blob.extend_from_slice(&field.to_be_bytes());
+        }
+        blob.extend_from_slice(&[0; FDT_RSVMAP_SIZE]);
+        blob.extend_from_slice(&self.structure);
+        blob.extend_from_slice(&self.strings);
+        blob
+    }
+}
+
+pub struct FdtProp {
+    pub name: String,
+    pub value: Vec<u8>,
+}
+
+impl FdtProp {
+    /// Format the value in DTS syntax: strings, cells or bytes
+    pub fn value_to_string(&self) -> String {
+        let v = &self.value;
+        if v.is_empty() {
+            return String::new();
+        }
+        let is_strings = v.last() == Some(&0)
+            && v[0] != 0
+            && !v.windows(2).any(|w| w == [0, 0])
+            && v.iter()
+                .all(|b| *b == 0 || b.is_ascii_graphic() || *b == b' ');
+        if is_strings {
+            let strings: Vec<String> = v[..v.len() - 1]
+                .split(|b| *b == 0)
+                .map(|s| format!("\"{}\"", String::from_utf8_lossy(s)))
+                .collect();
+            strings.join(", ")
+        } else if v.len().is_multiple_of(4) {
+            let cells: Vec<String> = v
+                .chunks(4)
+                .map(|c| format!("0x{:x}", u32::from_be_bytes([c[0], c[1], c[2], c[3]])))
+                .collect();
+            format!("<{}>", cells.join(" "))
+        } else {
+            let bytes: Vec<String> = v.iter().map(|b| format!("{b:02x}")).collect();
+            format!("[{}]", bytes.join(" "))
+        }
+    }
+}
+
+pub struct FdtNode {
+    pub name: String,
+    pub props: Vec<FdtProp>,
+    pub children: Vec<FdtNode>,
+}
+
+fn be32(blob: &[u8], off: usize) -> Result<u32, String> {
+    blob.get(off..off + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| format!("unexpected end of blob at offset 0x{off:x}"))
+}
+
+fn c_str(blob: &[u8], off: usize) -> Result<String, String> {
+    let s = blob.get(off..).unwrap_or_default();
+    match s.iter().position(|b| *b == 0) {
+        Some(len) => Ok(String::from_utf8_lossy(&s[..len]).into_owned()),
+        None => Err(format!("unterminated string at offset 0x{off:x}")),
+    }
+}
+
+/// Decode a device tree blob into a tree of nodes
+pub fn parse(blob: &[u8]) -> Result<FdtNode, String> {
+    if be32(blob, 0)? != FDT_MAGIC {
+        return Err("bad FDT magic".to_string());
+    }
+    let off_dt_struct = be32(blob, 8)? as usize;
+    let off_dt_strings = be32(blob, 12)? as usize;
+
+    // stack of nodes being built, the last one is the current node
+    let mut stack: Vec<FdtNode> = Vec::new();
+    let mut off = off_dt_struct;
+    loop {
+        let token = be32(blob, off)?;
+        off += 4;
+        match token {
+            FDT_BEGIN_NODE => {
+                let name = c_str(blob, off)?;
+                off = (off + name.len() + 1 + 3) & !3;
+                stack.push(FdtNode {
+                    name,
+                    props: Vec::new(),
+                    children: Vec::new(),
+                });
+            }
+            FDT_END_NODE => {
+                let node = stack.pop().ok_or("unbalanced FDT_END_NODE")?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(node),
+                    None => return Ok(node),
+                }
+            }
+            FDT_PROP => {
+                let len = be32(blob, off)? as usize;
+                let name_off = be32(blob, off + 4)? as usize;
+                off += 8;
+                let value = blob
+                    .get(off..off + len)
+                    .ok_or("property value is out of the blob")?
+                    .to_vec();
+                off = (off + len + 3) & !3;
+                let name = c_str(blob, off_dt_strings + name_off)?;
+                stack
+                    .last_mut()
+                    .ok_or("property outside of a node")?
+                    .props
+                    .push(FdtProp { name, value });
+            }
+            FDT_NOP => {}
+            FDT_END => return Err("FDT_END before the root node was closed".to_string()),
+            _ => {
+                return Err(format!(
+                    "unknown token 0x{token:x} at offset 0x{:x}",
+                    off - 4
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn child<'a>(node: &'a FdtNode, name: &str) -> &'a FdtNode {
+        node.children.iter().find(|c| c.name == name).unwrap()
+    }
+
+    fn prop<'a>(node: &'a FdtNode, name: &str) -> &'a [u8] {
+        &node.props.iter().find(|p| p.name == name).unwrap().value
+    }
+
+    #[test]
+    fn round_trip() {
+        let config = MachineConfig {
+            ram_size: 0x10_0000,
+            dtb_addr: Some(0x8000_f000),
+            ..MachineConfig::DEFAULT
+        };
+        let root = parse(&generate(&config)).unwrap();
+        assert_eq!(root.name, "");
+        assert_eq!(prop(&root, "#address-cells"), 2u32.to_be_bytes());
+        assert_eq!(prop(&root, "compatible"), b"kompusim\0");
+
+        let memory = child(&root, "memory@80000000");
+        let mut reg = config.ram_base.to_be_bytes().to_vec();
+        reg.extend_from_slice(&config.ram_size.to_be_bytes());
+        assert_eq!(prop(memory, "reg"), reg);
+
+        let cpu = child(child(&root, "cpus"), "cpu@0");
+        assert_eq!(prop(cpu, "riscv,isa"), b"rv64i\0");
+        let uart = child(child(&root, "soc"), "serial@10010000");
+        assert_eq!(prop(uart, "compatible"), b"sifive,uart0\0");
+        assert_eq!(
+            prop(child(&root, "chosen"), "stdout-path"),
+            b"/soc/serial@10010000\0"
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        let mut blob = generate(&MachineConfig::DEFAULT);
+        assert!(parse(&blob[..FDT_HEADER_SIZE]).is_err());
+        blob[0] = 0;
+        assert!(parse(&blob).is_err());
+    }
+}
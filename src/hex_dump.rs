@@ -0,0 +1,277 @@
+//! Parsing of pasted hex dumps: instruction words one per line,
+//! `xxd`, `od` and `objdump -d` output
+
+use crate::{number::parse_addr, rvc::instr_len};
+
+/// Instructions of a dump
+pub struct Dump {
+    /// Address of the first byte if the dump has addresses
+    pub first_addr: Option<u64>,
+    /// (offset from the first byte, instruction)
+    pub instrs: Vec<(u64, u32)>,
+    /// Bytes which don't form a complete instruction
+    pub warnings: Vec<String>,
+}
+
+pub fn parse_dump(text: &str) -> Result<Dump, String> {
+    let mut text_lines: Vec<(usize, &str)> = text
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or("").trim())
+        .enumerate()
+        .filter(|(_, line)| !is_header(line))
+        .collect();
+    // the last line of od is the end address only
+    if text_lines.len() > 1 && is_od_end(text_lines[text_lines.len() - 1].1) {
+        text_lines.pop();
+    }
+    // (address, bytes in memory order)
+    let mut lines = Vec::new();
+    for (i, line) in text_lines {
+        lines.push(parse_line(line).map_err(|err| format!("line {}: {err}", i + 1))?);
+    }
+    let first_addr = lines.iter().find_map(|(addr, _)| *addr);
+
+    let mut dump = Dump {
+        first_addr,
+        instrs: Vec::new(),
+        warnings: Vec::new(),
+    };
+    // contiguous bytes are split into instructions together as
+    // an instruction can continue on the next line
+    let mut start = 0;
+    let mut bytes = Vec::new();
+    for (addr, line_bytes) in lines {
+        let offset = match (addr, first_addr) {
+            (Some(addr), Some(first)) => addr.wrapping_sub(first),
+            _ => start + bytes.len() as u64,
+        };
+        if offset != start + bytes.len() as u64 {
+            split_instrs(start, &bytes, &mut dump);
+            start = offset;
+            bytes.clear();
+        }
+        bytes.extend(line_bytes);
+    }
+    split_instrs(start, &bytes, &mut dump);
+    Ok(dump)
+}
+
+/// Empty lines, objdump headers and labels, skipped repeated lines
+fn is_header(line: &str) -> bool {
+    line.is_empty()
+        || line.ends_with(">:")
+        || line.contains("file format")
+        || line.starts_with("Disassembly of")
+        || line == "..."
+        || line == "*"
+}
+
+/// Address in octal (7 digits) or hex (6 digits) alone
+fn is_od_end(line: &str) -> bool {
+    (line.len() == 6 || line.len() == 7) && line.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// (address, bytes in memory order) of a line
+fn parse_line(line: &str) -> Result<(Option<u64>, Vec<u8>), String> {
+    if let Some((addr, rest)) = line.split_once(':') {
+        let addr = parse_addr(addr).ok_or_else(|| format!("invalid address '{addr}'"))?;
+        // objdump separates the encoding and the mnemonic with tabs
+        if rest.starts_with('\t') {
+            let encoding = rest.trim_start_matches('\t').split('\t').next();
+            return Ok((
+                Some(addr),
+                values_to_bytes(encoding.unwrap_or("").split_whitespace())?,
+            ));
+        }
+        // xxd has groups of 2 bytes in memory order and a text column with
+        // a character per byte after two spaces
+        let rest = rest.trim_start();
+        let (groups, text) = rest.split_once("  ").unwrap_or((rest, ""));
+        if groups.split_whitespace().all(|group| group.len() <= 4) {
+            let bytes = memory_order_bytes(groups)?;
+            if text.len() == bytes.len() {
+                return Ok((Some(addr), bytes));
+            }
+        }
+        // "address: value" or objdump with tabs replaced with spaces
+        return Ok((Some(addr), values_to_bytes(groups.split_whitespace())?));
+    }
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    // od starts a line with the offset in octal (7 digits, default) or hex (6 digits, -A x),
+    // instructions have up to 4 or 8 digits
+    let lead = tokens[0];
+    let od_addr = match lead.len() {
+        7 if lead.chars().all(|c| ('0'..='7').contains(&c)) => u64::from_str_radix(lead, 8).ok(),
+        6 => parse_addr(lead),
+        _ => None,
+    };
+    let addr = match od_addr {
+        Some(addr) if tokens.len() > 1 => addr,
+        _ if lead.len() == 6 || lead.len() == 7 => {
+            return Err(format!(
+                "'{lead}' is neither an od offset nor an instruction word"
+            ))
+        }
+        // instruction words
+        _ => return Ok((None, values_to_bytes(tokens.into_iter())?)),
+    };
+    if tokens[1..].iter().all(|token| token.len() == 6) {
+        // od without -t prints octal 2-byte words
+        return Ok((Some(addr), octal_words_to_bytes(&tokens[1..])?));
+    }
+    Ok((Some(addr), values_to_bytes(tokens[1..].iter().copied())?))
+}
+
+/// Little-endian 2-byte words in octal (od default) as bytes in memory order
+fn octal_words_to_bytes(tokens: &[&str]) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    for token in tokens {
+        let word = u16::from_str_radix(token, 8).map_err(|_| {
+            format!("invalid octal word '{token}', use od -A x -t x1 or -t x2 for hex output")
+        })?;
+        bytes.extend_from_slice(&word.to_le_bytes());
+    }
+    Ok(bytes)
+}
+
+/// Little-endian values (objdump, od, instruction words) as bytes in memory order.
+/// The number of digits gives the width: up to 2 - a byte, up to 4 - 16 bits
+/// (e.g., a compressed instruction), otherwise 32 bits.
+fn values_to_bytes<'a>(tokens: impl Iterator<Item = &'a str>) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    for token in tokens {
+        let digits = token.trim_start_matches("0x").replace('_', "");
+        let value = u32::from_str_radix(&digits, 16)
+            .ok()
+            .filter(|_| digits.len() <= 8)
+            .ok_or_else(|| format!("invalid hex value '{token}'"))?;
+        let len = match digits.len() {
+            0..=2 => 1,
+            3..=4 => 2,
+            _ => 4,
+        };
+        bytes.extend_from_slice(&value.to_le_bytes()[..len]);
+    }
+    Ok(bytes)
+}
+
+/// Hex groups of xxd in memory order, e.g., "1305 1500"
+fn memory_order_bytes(groups: &str) -> Result<Vec<u8>, String> {
+    let digits: String = groups.split_whitespace().collect();
+    if !digits.len().is_multiple_of(2) || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("invalid hex bytes '{}'", groups.trim()));
+    }
+    Ok((0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap())
+        .collect())
+}
+
+/// Split bytes starting at the offset into instructions
+fn split_instrs(start: u64, bytes: &[u8], dump: &mut Dump) {
+    let mut i = 0;
+    while i + 2 <= bytes.len() {
+        let parcel = u16::from_le_bytes([bytes[i], bytes[i + 1]]) as u32;
+        let len = instr_len(parcel) as usize;
+        if i + len > bytes.len() {
+            break;
+        }
+        let instr = match len {
+            2 => parcel,
+            _ => u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap()),
+        };
+        dump.instrs.push((start + i as u64, instr));
+        i += len;
+    }
+    if i < bytes.len() {
+        dump.warnings.push(format!(
+            "{} trailing byte(s) at offset 0x{:x}",
+            bytes.len() - i,
+            start + i as u64
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instruction_words() {
+        let dump = parse_dump("00150513\n0x00000013\n8082 # ret\n").unwrap();
+        assert_eq!(dump.first_addr, None);
+        assert_eq!(
+            dump.instrs,
+            vec![(0, 0x0015_0513), (4, 0x0000_0013), (8, 0x8082)]
+        );
+    }
+
+    #[test]
+    fn xxd() {
+        let text = "00000000: 1305 1500 8280 9305  ........\n\
+                    00000008: 0000                                ..\n";
+        let dump = parse_dump(text).unwrap();
+        assert_eq!(dump.first_addr, Some(0));
+        assert_eq!(
+            dump.instrs,
+            vec![(0, 0x0015_0513), (4, 0x8082), (6, 0x0000_0593)]
+        );
+        assert!(dump.warnings.is_empty());
+    }
+
+    #[test]
+    fn objdump() {
+        let text = "t.o:     file format elf64-littleriscv\n\n\
+                    Disassembly of section .text:\n\n\
+                    0000000080000000 <_start>:\n\
+                    \x20   80000000:\t00150513          \taddi\ta0,a0,1\n\
+                    \x20   80000004:\t8082                \tret\n";
+        let dump = parse_dump(text).unwrap();
+        assert_eq!(dump.first_addr, Some(0x8000_0000));
+        assert_eq!(dump.instrs, vec![(0, 0x0015_0513), (4, 0x8082)]);
+    }
+
+    #[test]
+    fn od() {
+        // od -A x -t x2
+        let text = "000000 0513 0015 8082 0593\n000008 0000\n00000a\n";
+        let dump = parse_dump(text).unwrap();
+        assert_eq!(dump.first_addr, Some(0));
+        assert_eq!(
+            dump.instrs,
+            vec![(0, 0x0015_0513), (4, 0x8082), (6, 0x0000_0593)]
+        );
+        // od without options: octal addresses and octal words
+        let text = "0000000 002423 000025 100202\n0000006\n";
+        let dump = parse_dump(text).unwrap();
+        assert_eq!(dump.instrs, vec![(0, 0x0015_0513), (4, 0x8082)]);
+        // od -A x
+        let dump = parse_dump("000010 002423 000025\n000014\n").unwrap();
+        assert_eq!(dump.first_addr, Some(0x10));
+        assert_eq!(dump.instrs, vec![(0, 0x0015_0513)]);
+        assert!(parse_dump("0000000 002423 000025\n0000004 200000\n").is_err());
+    }
+
+    #[test]
+    fn mixed_widths() {
+        let dump = parse_dump("00150513 8082\n0x00000013\n").unwrap();
+        assert_eq!(dump.first_addr, None);
+        assert_eq!(
+            dump.instrs,
+            vec![(0, 0x0015_0513), (4, 0x8082), (6, 0x0000_0013)]
+        );
+        // the width comes from the digits, with or without 0x
+        let dump = parse_dump("0x13 05 0x15 00\n").unwrap();
+        assert_eq!(dump.instrs, vec![(0, 0x0015_0513)]);
+        // neither an od line nor instruction words
+        assert!(parse_dump("0123456\n00150513\n").is_err());
+        assert!(parse_dump("0912345 0513\n").is_err());
+    }
+
+    #[test]
+    fn trailing_bytes() {
+        let dump = parse_dump("00000000: 1305 1500 13  .....\n").unwrap();
+        assert_eq!(dump.instrs, vec![(0, 0x0015_0513)]);
+        assert_eq!(dump.warnings.len(), 1);
+    }
+}
@@ -1,4 +1,36 @@
-use kompusim::rv64i_disasm::{disasm, u32_bin4};
+use egui::{Color32, RichText};
+use kompusim::rv64i_disasm::disasm;
+
+use crate::{
+    assembler::assemble,
+    branch_arrows::branch_target,
+    hex_dump::{parse_dump, Dump},
+    instr_format::{bits, decode_fields, instr_format, opcode, sext, InstrFormat},
+    instr_list::InstrList,
+    isa::{csr_name, diagnose, exceptions, lookup, Extension, InstrDef},
+    listing::{listing_header, listing_line, ExportAction},
+    number::{parse_addr, parse_instr, parse_int},
+    regs::ABI_NAMES,
+    rvc::{decode_compressed, expand, is_compressed, CompressedInstr},
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::listing::save_listing;
+
+const INSTR_INPUT_HELP: &str = "Hex with or without 0x, binary (0b…), decimal (0d…) \
+or 2/4 bytes of a little-endian dump in memory order (e.g., 13 05 15 00). \
+Underscores are ignored.";
+
+/// Number of instructions kept in the history
+const HISTORY_LEN: usize = 50;
+
+/// Instruction kept in the history or pinned
+#[derive(serde::Deserialize, serde::Serialize, Clone, PartialEq)]
+struct SavedInstr {
+    instr: u32,
+    addr: u64,
+    note: String,
+}
 
 #[derive(serde::Deserialize, serde::Serialize)]
 #[serde(default)]
@@ -7,10 +39,56 @@ pub struct InstrDecoder {
     window_open: bool,
 
     instr_hex: String,
+    /// Why the typed instruction can't be parsed
+    #[serde(skip)]
+    instr_error: Option<String>,
+    /// Address of the instruction for PC-relative targets
+    addr_text: String,
+    #[serde(skip)]
+    addr: u64,
+    #[serde(skip)]
+    addr_error: Option<String>,
+    /// Batch mode decodes a pasted dump
+    batch_mode: bool,
+    dump_text: String,
+    /// Address of the first instruction of the dump, hex
+    base_text: String,
+    #[serde(skip)]
+    dump: Option<Result<Dump, String>>,
+    #[serde(skip)]
+    export_status: Option<String>,
+    /// Assembly text to encode
+    asm_text: String,
+    #[serde(skip)]
+    asm_error: Option<String>,
     #[serde(skip)]
     instr_disasm: String,
     #[serde(skip)]
-    instr_binary: String,
+    instr: u32,
+    /// Bits selected in the binary view: (first, last)
+    #[serde(skip)]
+    selection: Option<(u32, u32)>,
+    /// Bits are being selected by dragging
+    #[serde(skip)]
+    dragging: bool,
+    /// Value typed for the selected bits
+    #[serde(skip)]
+    bits_text: String,
+    #[serde(skip)]
+    bits_error: Option<String>,
+    /// The instruction is 16-bit
+    #[serde(skip)]
+    is_16bit: bool,
+    /// Decoded 16-bit instruction, None if it is illegal
+    #[serde(skip)]
+    compressed: Option<CompressedInstr>,
+    /// Recently decoded instructions, the newest first
+    history: Vec<SavedInstr>,
+    /// Instructions pinned with notes, e.g., examples prepared for a class
+    pinned: Vec<SavedInstr>,
+    /// The pinned instruction shown last, to step through the pins
+    #[serde(skip)]
+    pin_index: Option<usize>,
 }
 
 impl Default for InstrDecoder {
@@ -18,8 +96,28 @@ impl Default for InstrDecoder {
         InstrDecoder {
             window_open: true,
             instr_hex: String::with_capacity(16),
+            instr_error: None,
+            addr_text: String::new(),
+            addr: 0,
+            addr_error: None,
+            batch_mode: false,
+            dump_text: String::new(),
+            base_text: String::new(),
+            dump: None,
+            export_status: None,
+            asm_text: String::new(),
+            asm_error: None,
             instr_disasm: String::new(),
-            instr_binary: String::new(),
+            instr: 0,
+            selection: None,
+            dragging: false,
+            bits_text: String::new(),
+            bits_error: None,
+            is_16bit: false,
+            compressed: None,
+            history: Vec::new(),
+            pinned: Vec::new(),
+            pin_index: None,
         }
     }
 }
@@ -29,49 +127,911 @@ impl InstrDecoder {
         self.window_open = true;
     }
 
-    pub fn show(&mut self, ctx: &egui::Context) {
+    /// Decode the instruction at the address, e.g., opened from the instruction list
+    pub fn decode_at(&mut self, instr: u32, addr: u64) {
+        self.load(instr, addr);
+        self.remember();
+        self.window_open = true;
+    }
+
+    /// `instr_list` - resolves branch targets to labels and symbols
+    pub fn show(&mut self, ctx: &egui::Context, instr_list: &InstrList) {
         let mut open = self.window_open;
         egui::Window::new("Instruction decoder")
             .open(&mut open)
             .resizable(true)
             .default_width(400.0)
             .show(ctx, |ui| {
-                self.show_window_content(ui);
+                self.show_window_content(ui, instr_list);
             });
         self.window_open = open;
     }
 
-    fn show_window_content(&mut self, ui: &mut egui::Ui) {
+    fn show_window_content(&mut self, ui: &mut egui::Ui, instr_list: &InstrList) {
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.batch_mode, false, "Single");
+            ui.selectable_value(&mut self.batch_mode, true, "Batch")
+                .on_hover_text("Decode a pasted hex dump");
+        });
+        if self.batch_mode {
+            self.show_batch(ui, instr_list);
+            return;
+        }
         egui::Grid::new("decode_instr_grid")
             .num_columns(2)
             .spacing([40.0, 4.0])
             .striped(true)
             .show(ui, |ui| {
                 ui.label("Instruction");
-                let response = ui.add(
-                    egui::TextEdit::singleline(&mut self.instr_hex).hint_text("instruction in hex"),
-                );
-                if response.changed() || self.instr_disasm.len() == 0 {
-                    let instr = hex_to_u32(&self.instr_hex);
-                    self.instr_disasm = disasm(instr, 0x0); // TODO: add address
-                    self.instr_binary = u32_bin4(instr);
-                }
+                ui.vertical(|ui| {
+                    let response = ui
+                        .horizontal(|ui| {
+                            let response = ui
+                                .add(
+                                    egui::TextEdit::singleline(&mut self.instr_hex)
+                                        .hint_text("instruction in hex"),
+                                )
+                                .on_hover_text(INSTR_INPUT_HELP);
+                            if ui.small_button("📌").on_hover_text("Pin").clicked() {
+                                self.pin();
+                            }
+                            response
+                        })
+                        .inner;
+                    if response.changed() || self.instr_disasm.is_empty() {
+                        self.decode();
+                    }
+                    if response.lost_focus() {
+                        self.remember();
+                    }
+                    if let Some(err) = &self.instr_error {
+                        ui.colored_label(ui.visuals().error_fg_color, err);
+                    } else if !self.instr_hex.trim().chars().all(|c| c.is_ascii_hexdigit()) {
+                        // show how other notations were interpreted
+                        ui.weak(format!("= 0x{:08x}", self.instr));
+                    }
+                });
+                ui.end_row();
+                ui.label("Address");
+                ui.vertical(|ui| {
+                    let response = ui.add(
+                        egui::TextEdit::singleline(&mut self.addr_text)
+                            .hint_text("instruction address in hex"),
+                    );
+                    if response.changed() {
+                        self.decode();
+                    }
+                    if let Some(err) = &self.addr_error {
+                        ui.colored_label(ui.visuals().error_fg_color, err);
+                    }
+                });
                 ui.end_row();
-                ui.label("Binary");
+                ui.label("Assemble");
                 ui.vertical(|ui| {
-                    //ui.label(RichText::new("").monospace());
-                    ui.monospace("31   27   23   19   15   11   7    3   ");
-                    ui.monospace(&self.instr_binary);
+                    let response = ui.add(
+                        egui::TextEdit::singleline(&mut self.asm_text)
+                            .hint_text("e.g., addi a0, a0, 1"),
+                    );
+                    if response.changed() {
+                        match assemble(&self.asm_text) {
+                            Ok(instr) => {
+                                self.instr_hex = format!("{instr:08x}");
+                                self.asm_error = None;
+                                self.decode();
+                            }
+                            Err(err) => self.asm_error = Some(err),
+                        }
+                    }
+                    if response.lost_focus() && self.asm_error.is_none() {
+                        self.remember();
+                    }
+                    if let Some(err) = &self.asm_error {
+                        ui.colored_label(ui.visuals().error_fg_color, err);
+                    }
+                });
+                ui.end_row();
+                ui.label("Format");
+                ui.label(match (&self.compressed, self.is_16bit) {
+                    (Some(c), _) => c.format.name(),
+                    (None, true) => "illegal compressed",
+                    (None, false) => instr_format(self.instr).name(),
                 });
                 ui.end_row();
+                ui.label("Encoding");
+                self.show_encoding(ui);
+                ui.end_row();
                 ui.label("Assembly");
-                ui.label(&self.instr_disasm);
+                ui.vertical(|ui| {
+                    ui.label(&self.instr_disasm);
+                    if let Some(reason) = self.illegal_reason() {
+                        ui.colored_label(ui.visuals().warn_fg_color, reason);
+                    }
+                });
                 ui.end_row();
+                if let Some(target) = self.target() {
+                    ui.label("Target");
+                    match instr_list.label_at(target) {
+                        Some(label) => ui.monospace(format!("0x{target:x} <{label}>")),
+                        None => ui.monospace(format!("0x{target:x}")),
+                    };
+                    ui.end_row();
+                }
+                if let Some(c) = &self.compressed {
+                    ui.label("Compressed");
+                    ui.label(c.name);
+                    ui.end_row();
+                    ui.label("Expands to");
+                    ui.monospace(format!("{:08x}", c.expanded));
+                    ui.end_row();
+                }
             });
+        ui.separator();
+        self.show_fields_table(ui);
+        self.show_explanation(ui);
+        self.show_saved(ui);
+    }
+
+    /// Pinned instructions with notes and the history
+    fn show_saved(&mut self, ui: &mut egui::Ui) {
+        let mut load = None;
+        let mut pin = None;
+        let mut unpin = None;
+        let mut move_up = None;
+        egui::CollapsingHeader::new(format!("Pinned ({})", self.pinned.len()))
+            .id_source("decode_pinned")
+            .default_open(true)
+            .show(ui, |ui| {
+                let count = self.pinned.len();
+                if count == 0 {
+                    ui.label("Pin instructions with 📌 to keep them with notes");
+                    return;
+                }
+                ui.horizontal(|ui| {
+                    let step = if ui.button("⏴ Previous").clicked() {
+                        Some(
+                            self.pin_index
+                                .map_or(count - 1, |i| (i + count - 1) % count),
+                        )
+                    } else if ui.button("Next ⏵").clicked() {
+                        Some(self.pin_index.map_or(0, |i| (i + 1) % count))
+                    } else {
+                        None
+                    };
+                    if let Some(i) = step {
+                        self.pin_index = Some(i);
+                        load = Some((self.pinned[i].instr, self.pinned[i].addr));
+                    }
+                });
+                egui::Grid::new("decode_pinned_grid")
+                    .num_columns(4)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (i, saved) in self.pinned.iter_mut().enumerate() {
+                            let hex = RichText::new(instr_hex(saved.instr)).monospace();
+                            if ui
+                                .selectable_label(self.pin_index == Some(i), hex)
+                                .clicked()
+                            {
+                                self.pin_index = Some(i);
+                                load = Some((saved.instr, saved.addr));
+                            }
+                            ui.monospace(disasm_any(saved.instr, saved.addr));
+                            ui.add(
+                                egui::TextEdit::singleline(&mut saved.note)
+                                    .hint_text("note")
+                                    .desired_width(200.0),
+                            );
+                            ui.horizontal(|ui| {
+                                if i > 0 && ui.small_button("⏶").on_hover_text("Move up").clicked()
+                                {
+                                    move_up = Some(i);
+                                }
+                                if ui.small_button("✖").on_hover_text("Unpin").clicked() {
+                                    unpin = Some(i);
+                                }
+                            });
+                            ui.end_row();
+                        }
+                    });
+            });
+        egui::CollapsingHeader::new(format!("History ({})", self.history.len()))
+            .id_source("decode_history")
+            .show(ui, |ui| {
+                if self.history.is_empty() {
+                    ui.label("Decoded instructions appear here");
+                    return;
+                }
+                if ui.button("Clear").clicked() {
+                    self.history.clear();
+                    return;
+                }
+                egui::Grid::new("decode_history_grid")
+                    .num_columns(3)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (i, saved) in self.history.iter().enumerate() {
+                            let hex = RichText::new(instr_hex(saved.instr)).monospace();
+                            if ui.button(hex).clicked() {
+                                load = Some((saved.instr, saved.addr));
+                            }
+                            ui.monospace(disasm_any(saved.instr, saved.addr));
+                            if ui.small_button("📌").on_hover_text("Pin").clicked() {
+                                pin = Some(i);
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+        if let Some((instr, addr)) = load {
+            self.load(instr, addr);
+        }
+        if let Some(i) = pin {
+            let saved = self.history[i].clone();
+            if !self.pinned.contains(&saved) {
+                self.pinned.push(saved);
+            }
+        }
+        if let Some(i) = move_up {
+            self.pinned.swap(i - 1, i);
+            self.pin_index = None;
+        }
+        if let Some(i) = unpin {
+            self.pinned.remove(i);
+            self.pin_index = None;
+        }
+    }
+
+    /// Pin the decoded instruction
+    fn pin(&mut self) {
+        if self.instr_error.is_some() || self.instr_hex.trim().is_empty() {
+            return;
+        }
+        let pinned = self
+            .pinned
+            .iter()
+            .position(|saved| saved.instr == self.instr && saved.addr == self.addr);
+        self.pin_index = Some(pinned.unwrap_or_else(|| {
+            self.pinned.push(SavedInstr {
+                instr: self.instr,
+                addr: self.addr,
+                note: String::new(),
+            });
+            self.pinned.len() - 1
+        }));
+    }
+
+    /// Add the decoded instruction to the history
+    fn remember(&mut self) {
+        if self.instr_error.is_some() || self.instr_hex.trim().is_empty() {
+            return;
+        }
+        self.history
+            .retain(|saved| saved.instr != self.instr || saved.addr != self.addr);
+        self.history.insert(
+            0,
+            SavedInstr {
+                instr: self.instr,
+                addr: self.addr,
+                note: String::new(),
+            },
+        );
+        self.history.truncate(HISTORY_LEN);
+    }
+
+    fn load(&mut self, instr: u32, addr: u64) {
+        self.instr_hex = instr_hex(instr);
+        self.addr_text = format!("{addr:x}");
+        self.decode();
+    }
+
+    /// Operation, immediate reconstruction, ISA extension and exceptions
+    fn show_explanation(&self, ui: &mut egui::Ui) {
+        // compressed instructions are explained by their expansion
+        let instr = match (&self.compressed, self.is_16bit) {
+            (Some(c), _) => c.expanded,
+            (None, true) => return,
+            (None, false) => self.instr,
+        };
+        egui::CollapsingHeader::new("Explanation")
+            .default_open(true)
+            .show(ui, |ui| {
+                let def = match lookup(instr) {
+                    Some(def) => def,
+                    None => {
+                        ui.label(format!(
+                            "Not decoded: {}. Raises Illegal instruction exception.",
+                            diagnose(instr)
+                        ));
+                        return;
+                    }
+                };
+                egui::Grid::new("decode_explain_grid")
+                    .num_columns(2)
+                    .spacing([40.0, 4.0])
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("Extension");
+                        ui.vertical(|ui| {
+                            match &self.compressed {
+                                Some(_) => ui.label(format!("C, expands to {}", def.ext.name())),
+                                None => ui.label(def.ext.name()),
+                            };
+                            if self.compressed.is_some() || !def.ext.simulated() {
+                                ui.colored_label(
+                                    ui.visuals().warn_fg_color,
+                                    "not implemented by the simulator, \
+                                     raises Illegal instruction exception",
+                                );
+                            }
+                        });
+                        ui.end_row();
+                        ui.label("Operation");
+                        ui.monospace(def.pseudo);
+                        ui.end_row();
+                        if let Some(steps) = imm_steps(def, instr) {
+                            ui.label("Immediate");
+                            ui.vertical(|ui| {
+                                for step in steps {
+                                    ui.monospace(step);
+                                }
+                            });
+                            ui.end_row();
+                        }
+                        ui.label("Exceptions");
+                        ui.vertical(|ui| {
+                            for exception in exceptions(def) {
+                                ui.label(*exception);
+                            }
+                            if exceptions(def).is_empty() {
+                                ui.label("none");
+                            }
+                            if def.ext == Extension::M && !def.name.starts_with("mul") {
+                                ui.label("division by zero and overflow don't trap");
+                            }
+                        });
+                        ui.end_row();
+                    });
+            });
+    }
+
+    /// Why the instruction can't be decoded, None if it can
+    fn illegal_reason(&self) -> Option<String> {
+        match (&self.compressed, self.is_16bit) {
+            (Some(_), _) => None,
+            (None, true) if self.instr == 0 => {
+                Some("all-zeros encoding is defined as illegal".to_string())
+            }
+            (None, true) => Some("reserved compressed encoding".to_string()),
+            (None, false) => match lookup(self.instr) {
+                Some(_) => None,
+                None => Some(diagnose(self.instr)),
+            },
+        }
+    }
+
+    /// Decode the typed instruction, the previous one stays on errors
+    fn decode(&mut self) {
+        match parse_addr(&self.addr_text) {
+            Some(addr) => {
+                self.addr = addr;
+                self.addr_error = None;
+            }
+            None if self.addr_text.trim().is_empty() => {
+                self.addr = 0;
+                self.addr_error = None;
+            }
+            None => self.addr_error = Some(format!("invalid address '{}'", self.addr_text)),
+        }
+        if self.instr_hex.trim().is_empty() {
+            self.instr_error = None;
+            return;
+        }
+        match parse_instr(&self.instr_hex) {
+            Ok(instr) => {
+                self.instr = instr;
+                self.instr_error = None;
+            }
+            Err(err) => {
+                self.instr_error = Some(err);
+                return;
+            }
+        }
+        // 32-bit instructions always have the lowest bits set
+        self.is_16bit = self.instr <= 0xffff && is_compressed(self.instr);
+        self.compressed = match self.is_16bit {
+            true => decode_compressed(self.instr as u16),
+            false => None,
+        };
+        self.instr_disasm = disasm_any(self.instr, self.addr);
+        let max_bit = if self.is_16bit { 15 } else { 31 };
+        if self.selected_bits().is_some_and(|(hi, _)| hi > max_bit) {
+            self.selection = None;
+        }
+    }
+
+    /// Decode a pasted dump: instruction words, xxd, od or objdump output
+    fn show_batch(&mut self, ui: &mut egui::Ui, instr_list: &InstrList) {
+        ui.horizontal(|ui| {
+            ui.label("Base address");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.base_text)
+                    .hint_text("from the dump")
+                    .desired_width(140.0),
+            );
+        });
+        let response = ui.add(
+            egui::TextEdit::multiline(&mut self.dump_text)
+                .code_editor()
+                .desired_rows(6)
+                .desired_width(f32::INFINITY)
+                .hint_text("hex words one per line, xxd, od or objdump output"),
+        );
+        if response.changed() || self.dump.is_none() {
+            self.dump = Some(parse_dump(&self.dump_text));
+            self.export_status = None;
+        }
+        let dump = match &self.dump {
+            Some(Ok(dump)) => dump,
+            Some(Err(err)) => {
+                ui.colored_label(ui.visuals().error_fg_color, err);
+                return;
+            }
+            None => return,
+        };
+        let base = match self.base_text.trim() {
+            "" => dump.first_addr.unwrap_or(0),
+            text => match parse_addr(text) {
+                Some(base) => base,
+                None => {
+                    let err = format!("invalid base address '{text}'");
+                    ui.colored_label(ui.visuals().error_fg_color, err);
+                    return;
+                }
+            },
+        };
+        for warning in &dump.warnings {
+            ui.colored_label(ui.visuals().warn_fg_color, warning);
+        }
+
+        let mut export = None;
+        ui.horizontal(|ui| {
+            ui.label(format!("{} instructions", dump.instrs.len()));
+            #[cfg(not(target_arch = "wasm32"))]
+            if ui.button("Save...").clicked() {
+                export = Some(ExportAction::Save);
+            }
+            if ui.button("Copy to clipboard").clicked() {
+                export = Some(ExportAction::Copy);
+            }
+            if let Some(status) = &self.export_status {
+                ui.label(status);
+            }
+        });
+        if let Some(action) = export {
+            let mut text = listing_header("dump");
+            for (offset, instr) in &dump.instrs {
+                let addr = base.wrapping_add(*offset);
+                listing_line(&mut text, addr, *instr, instr_list.label_at(addr), None);
+            }
+            self.export_status = match action {
+                #[cfg(not(target_arch = "wasm32"))]
+                ExportAction::Save => save_listing("dump", &text),
+                ExportAction::Copy => {
+                    ui.output_mut(|o| o.copied_text = text);
+                    Some("copied to the clipboard".to_string())
+                }
+            };
+        }
+
+        let dump = match &self.dump {
+            Some(Ok(dump)) => dump,
+            _ => return,
+        };
+        egui::ScrollArea::vertical()
+            .max_height(400.0)
+            .auto_shrink([false, true])
+            .show(ui, |ui| {
+                egui::Grid::new("decode_batch_grid")
+                    .num_columns(4)
+                    .spacing([20.0, 2.0])
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("Address");
+                        ui.strong("Label");
+                        ui.strong("Encoding");
+                        ui.strong("Assembly");
+                        ui.end_row();
+                        for (offset, instr) in &dump.instrs {
+                            let addr = base.wrapping_add(*offset);
+                            ui.monospace(format!("{addr:016x}"));
+                            ui.monospace(instr_list.label_at(addr).unwrap_or(""));
+                            if is_compressed(*instr) {
+                                ui.monospace(format!("{instr:04x}"));
+                            } else {
+                                ui.monospace(format!("{instr:08x}"));
+                            }
+                            ui.monospace(disasm(expand(*instr), addr));
+                            ui.end_row();
+                        }
+                    });
+            });
+    }
+
+    /// Absolute target of a branch or jump, or the address computed by auipc
+    fn target(&self) -> Option<u64> {
+        let instr = match (&self.compressed, self.is_16bit) {
+            (Some(c), _) => c.expanded,
+            (None, true) => return None,
+            (None, false) => self.instr,
+        };
+        if instr & 0x7f == opcode::AUIPC {
+            let imm = decode_fields(instr).imm?;
+            return Some(self.addr.wrapping_add(imm as u64));
+        }
+        branch_target(instr, self.addr).map(|(target, _)| target)
+    }
+
+    /// Set the instruction edited in the binary view
+    fn set_instr(&mut self, instr: u32) {
+        self.instr_hex = if self.is_16bit {
+            format!("{instr:04x}")
+        } else {
+            format!("{instr:08x}")
+        };
+        self.decode();
+    }
+
+    /// Fields as colored bit groups with bit numbers above and names below.
+    /// Click a bit to flip it, drag over bits or click a field name to select
+    /// bits and type their value.
+    fn show_encoding(&mut self, ui: &mut egui::Ui) {
+        let selected = self.selected_bits();
+        let mut bit_rects = Vec::new();
+        let mut flip = None;
+        let mut select = None;
+        ui.vertical(|ui| {
+            ui.horizontal(|ui| {
+                ui.spacing_mut().item_spacing.x = 6.0;
+                for (name, hi, lo) in self.field_layout() {
+                    let width = (hi - lo + 1) as usize;
+                    let color = field_color(name);
+                    // bit numbers of the field ends over the first and last bits,
+                    // only the highest one if the field is too narrow
+                    let (hi_text, lo_text) = (hi.to_string(), lo.to_string());
+                    let ruler = if width > hi_text.len() + lo_text.len() {
+                        format!("{hi_text:<pad$}{lo_text}", pad = width - lo_text.len())
+                    } else {
+                        hi_text
+                    };
+                    ui.vertical(|ui| {
+                        ui.label(RichText::new(ruler).monospace().small().weak());
+                        ui.horizontal(|ui| {
+                            ui.spacing_mut().item_spacing.x = 0.0;
+                            for bit in (*lo..=*hi).rev() {
+                                let mut text = RichText::new((self.instr >> bit & 1).to_string())
+                                    .monospace()
+                                    .color(color);
+                                if selected.is_some_and(|(hi, lo)| (lo..=hi).contains(&bit)) {
+                                    text = text.background_color(ui.visuals().selection.bg_fill);
+                                }
+                                let response = ui
+                                    .add(
+                                        egui::Label::new(text).sense(egui::Sense::click_and_drag()),
+                                    )
+                                    .on_hover_text(format!("bit {bit}, click to flip"));
+                                if response.clicked() {
+                                    flip = Some(bit);
+                                } else if response.drag_started() {
+                                    self.selection = Some((bit, bit));
+                                    self.dragging = true;
+                                }
+                                bit_rects.push((bit, response.rect));
+                            }
+                        });
+                        let response = ui
+                            .add(
+                                egui::Label::new(RichText::new(*name).small().color(color))
+                                    .sense(egui::Sense::click()),
+                            )
+                            .on_hover_text("click to edit the field");
+                        if response.clicked() {
+                            select = Some((*hi, *lo));
+                        }
+                    });
+                }
+            });
+
+            // labels don't get hovered while another one is dragged,
+            // find the bit under the pointer instead
+            if self.dragging {
+                let (down, pos) =
+                    ui.input(|i| (i.pointer.primary_down(), i.pointer.interact_pos()));
+                if !down {
+                    self.dragging = false;
+                } else if let Some(pos) = pos {
+                    if let Some((bit, _)) = bit_rects
+                        .iter()
+                        .find(|(_, rect)| rect.x_range().contains(&pos.x))
+                    {
+                        self.selection = self.selection.map(|(start, _)| (start, *bit));
+                    }
+                }
+                if let Some((hi, lo)) = self.selected_bits() {
+                    self.bits_text = bits(self.instr, hi, lo).to_string();
+                    self.bits_error = None;
+                }
+            }
+            if let Some((hi, lo)) = select {
+                self.selection = Some((hi, lo));
+                self.bits_text = bits(self.instr, hi, lo).to_string();
+                self.bits_error = None;
+            }
+            if let Some(bit) = flip {
+                self.selection = None;
+                self.set_instr(self.instr ^ 1 << bit);
+            }
+
+            if let Some((hi, lo)) = self.selected_bits().filter(|_| !self.dragging) {
+                ui.horizontal(|ui| {
+                    ui.label(format!("bits [{hi}:{lo}] ="));
+                    let response = ui.add(
+                        egui::TextEdit::singleline(&mut self.bits_text)
+                            .desired_width(120.0)
+                            .hint_text("value"),
+                    );
+                    if response.changed() {
+                        match bits_value(&self.bits_text, hi - lo + 1) {
+                            Ok(value) => {
+                                self.bits_error = None;
+                                let mask = (((1u64 << (hi - lo + 1)) - 1) as u32) << lo;
+                                self.set_instr(self.instr & !mask | value << lo);
+                            }
+                            Err(err) => self.bits_error = Some(err),
+                        }
+                    }
+                    if ui
+                        .small_button("✖")
+                        .on_hover_text("clear selection")
+                        .clicked()
+                    {
+                        self.selection = None;
+                    }
+                });
+                if let Some(err) = &self.bits_error {
+                    ui.colored_label(ui.visuals().error_fg_color, err);
+                }
+            }
+        });
+    }
+
+    /// Selected bit range (hi, lo)
+    fn selected_bits(&self) -> Option<(u32, u32)> {
+        self.selection.map(|(a, b)| (a.max(b), a.min(b)))
+    }
+
+    /// Fields of the instruction format: (name, hi, lo)
+    fn field_layout(&self) -> &'static [(&'static str, u32, u32)] {
+        match (&self.compressed, self.is_16bit) {
+            (Some(c), _) => c.format.fields(),
+            (None, true) => &[("illegal", 15, 2), ("op", 1, 0)],
+            (None, false) => instr_format(self.instr).fields(),
+        }
+    }
+
+    fn show_fields_table(&self, ui: &mut egui::Ui) {
+        egui::Grid::new("decode_fields_grid")
+            .num_columns(4)
+            .spacing([20.0, 4.0])
+            .striped(true)
+            .show(ui, |ui| {
+                ui.strong("Field");
+                ui.strong("Bits");
+                ui.strong("Value");
+                ui.strong("Meaning");
+                ui.end_row();
+                for (name, hi, lo) in self.field_layout() {
+                    let value = bits(self.instr, *hi, *lo);
+                    ui.label(RichText::new(*name).monospace().color(field_color(name)));
+                    if hi == lo {
+                        ui.monospace(format!("[{hi}]"));
+                    } else {
+                        ui.monospace(format!("[{hi}:{lo}]"));
+                    }
+                    ui.monospace(value.to_string());
+                    ui.label(self.field_meaning(name, value));
+                    ui.end_row();
+                }
+                if self.is_16bit {
+                    return;
+                }
+                if let Some(imm) = decode_fields(self.instr).imm {
+                    ui.label(RichText::new("imm").monospace().color(field_color("imm")));
+                    ui.label("");
+                    ui.monospace(imm.to_string());
+                    ui.label(self.immediate_meaning(imm));
+                    ui.end_row();
+                }
+            });
+    }
+
+    /// Meaning of the field value, e.g., register name or selected operation
+    fn field_meaning(&self, name: &str, value: u32) -> String {
+        let reg = value as usize;
+        if let Some(c) = &self.compressed {
+            // c.fld, c.fsd, c.fldsp and c.fsdsp move floating-point registers
+            let fp =
+                c.name.starts_with("c.f") && (name.starts_with("rd") || name.starts_with("rs2"));
+            let reg = if name.ends_with('\'') { reg + 8 } else { reg };
+            return match name {
+                "op" => format!("quadrant C{value}"),
+                _ if name.starts_with("funct") => format!("selects {}", c.name),
+                _ if name.starts_with("rd") || name.starts_with("rs") => reg_meaning(reg, fp),
+                _ => String::new(),
+            };
+        }
+        if self.is_16bit {
+            return String::new();
+        }
+        let op = self.instr & 0x7f;
+        let format = instr_format(self.instr);
+        match name {
+            "opcode" => opcode::name(value).unwrap_or("unknown opcode").to_string(),
+            // CSR instructions with an immediate have a 5-bit zimm instead of rs1
+            "rs1" if op == opcode::SYSTEM && bits(self.instr, 14, 12) >= 5 => {
+                format!("zimm = {value}")
+            }
+            "rd" | "rs1" | "rs2" | "rs3" => {
+                let fp = format == InstrFormat::R4
+                    || (op == opcode::LOAD_FP && name == "rd")
+                    || (op == opcode::STORE_FP && name == "rs2");
+                reg_meaning(reg, fp)
+            }
+            "rm" => format!("rounding mode {value}"),
+            _ if name.starts_with("funct") => match lookup(self.instr) {
+                Some(def) => format!("selects {}", def.name),
+                None => "unknown operation".to_string(),
+            },
+            _ => String::new(),
+        }
+    }
+
+    fn immediate_meaning(&self, imm: i64) -> String {
+        let op = self.instr & 0x7f;
+        match instr_format(self.instr) {
+            InstrFormat::B | InstrFormat::J => format!("pc {imm:+}"),
+            InstrFormat::U => format!("0x{:x} << 12", (imm as u64 >> 12) & 0xf_ffff),
+            InstrFormat::I if op == opcode::SYSTEM && bits(self.instr, 14, 12) != 0 => {
+                let csr = imm as u32 & 0xfff;
+                match csr_name(csr) {
+                    Some(name) => format!("CSR 0x{csr:03x} ({name})"),
+                    None => format!("CSR 0x{csr:03x}"),
+                }
+            }
+            InstrFormat::I
+                if (op == opcode::OP_IMM || op == opcode::OP_IMM_32)
+                    && bits(self.instr, 13, 12) == 0b01 =>
+            {
+                format!("shamt {}", imm & 0x3f)
+            }
+            _ => format!("0x{:x}", imm),
+        }
+    }
+}
+
+/// Register name, `fp` - floating-point register
+fn reg_meaning(reg: usize, fp: bool) -> String {
+    if fp {
+        format!("f{reg}")
+    } else {
+        format!("{} (x{reg})", ABI_NAMES[reg])
+    }
+}
+
+/// Colors of the field kinds
+fn field_color(name: &str) -> Color32 {
+    if name.starts_with("op") {
+        Color32::GRAY
+    } else if name.starts_with("rd") {
+        Color32::from_rgb(80, 180, 90)
+    } else if name.starts_with("rs1") {
+        Color32::from_rgb(90, 150, 230)
+    } else if name.starts_with("rs2") {
+        Color32::from_rgb(230, 140, 50)
+    } else if name.starts_with("rs3") {
+        Color32::from_rgb(180, 100, 220)
+    } else if name.starts_with("funct") || name == "rm" {
+        Color32::from_rgb(200, 180, 40)
+    } else {
+        Color32::from_rgb(220, 80, 80)
     }
 }
 
-/// Convert hex str (e.g, "0x9393") to u32
-fn hex_to_u32(hex_str: &str) -> u32 {
-    u32::from_str_radix(hex_str.trim_start_matches("0x"), 16).unwrap_or_default()
+/// "name[hi:lo]" or "name[bit]"
+fn bit_range(name: &str, hi: u32, lo: u32) -> String {
+    if hi == lo {
+        format!("{name}[{hi}]")
+    } else {
+        format!("{name}[{hi}:{lo}]")
+    }
+}
+
+/// Reconstruction of the immediate from the instruction bits step by step
+fn imm_steps(def: &InstrDef, instr: u32) -> Option<Vec<String>> {
+    // ecall, ebreak and fences have no immediate operand
+    if def.mask == 0xffff_ffff || instr & 0x7f == opcode::MISC_MEM {
+        return None;
+    }
+    let (width, pieces) = def.format.imm_layout()?;
+    let mut steps = Vec::new();
+    let mut imm = 0;
+    for (imm_hi, imm_lo, instr_hi) in pieces {
+        let instr_lo = instr_hi - (imm_hi - imm_lo);
+        let value = bits(instr, *instr_hi, instr_lo);
+        let len = (imm_hi - imm_lo + 1) as usize;
+        imm |= value << imm_lo;
+        steps.push(format!(
+            "{:<10} = {:<12} = {value:0len$b}",
+            bit_range("imm", *imm_hi, *imm_lo),
+            bit_range("instr", *instr_hi, instr_lo)
+        ));
+    }
+    match def.format {
+        InstrFormat::B | InstrFormat::J => {
+            steps.push(format!("{:<10} = 0 (offsets are multiples of 2)", "imm[0]"))
+        }
+        InstrFormat::U => steps.push(format!("{:<10} = 0", "imm[11:0]")),
+        _ => {}
+    }
+    steps.push(format!(
+        "imm = {imm:0len$b} ({width} bits)",
+        len = width as usize
+    ));
+    let funct3 = bits(instr, 14, 12);
+    let op = instr & 0x7f;
+    if op == opcode::SYSTEM {
+        steps.push(format!("CSR number, not sign-extended: 0x{imm:03x}"));
+    } else if (op == opcode::OP_IMM || op == opcode::OP_IMM_32) && (funct3 == 1 || funct3 == 5) {
+        steps.push(format!(
+            "shamt = imm[5:0] = {}, imm[11:6] selects the shift",
+            imm & 0x3f
+        ));
+    } else {
+        let value = sext(imm, width);
+        steps.push(format!(
+            "sign-extend bit {}: 0x{:016x} = {value}",
+            width - 1,
+            value as u64
+        ));
+    }
+    Some(steps)
+}
+
+/// Value of `width` bits, negative values are two's complement
+fn bits_value(text: &str, width: u32) -> Result<u32, String> {
+    let value = parse_int(text.trim()).ok_or_else(|| format!("invalid number '{text}'"))?;
+    let (min, max) = (-(1i64 << (width - 1)), (1i64 << width) - 1);
+    if value < min || value > max {
+        return Err(format!("{value} doesn't fit in {width} bits"));
+    }
+    Ok((value as u32) & (((1u64 << width) - 1) as u32))
+}
+
+fn instr_hex(instr: u32) -> String {
+    if is_compressed(instr) {
+        format!("{instr:04x}")
+    } else {
+        format!("{instr:08x}")
+    }
+}
+
+/// Assembly of a 16 or 32-bit instruction
+fn disasm_any(instr: u32, addr: u64) -> String {
+    if instr <= 0xffff && is_compressed(instr) {
+        match decode_compressed(instr as u16) {
+            Some(c) => disasm(c.expanded, addr),
+            None => "illegal compressed instruction".to_string(),
+        }
+    } else {
+        disasm(instr, addr)
+    }
 }
@@ -0,0 +1,301 @@
+//! Instruction formats and fields of 32-bit RISC-V instructions
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum InstrFormat {
+    R,
+    I,
+    S,
+    B,
+    U,
+    J,
+    /// Fused multiply-add with three source registers
+    R4,
+    /// Opcode of an unknown or reserved instruction
+    Unknown,
+}
+
+/// Bits [imm hi:imm lo] of an immediate come from the instruction bits
+/// starting at instr hi: (imm hi, imm lo, instr hi)
+pub type ImmPiece = (u32, u32, u32);
+
+impl InstrFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            InstrFormat::R => "R (register)",
+            InstrFormat::I => "I (immediate)",
+            InstrFormat::S => "S (store)",
+            InstrFormat::B => "B (branch)",
+            InstrFormat::U => "U (upper immediate)",
+            InstrFormat::J => "J (jump)",
+            InstrFormat::R4 => "R4 (fused multiply-add)",
+            InstrFormat::Unknown => "unknown",
+        }
+    }
+
+    /// Fields of the format from the most significant bits: (name, hi, lo)
+    pub fn fields(self) -> &'static [(&'static str, u32, u32)] {
+        match self {
+            InstrFormat::R => &[
+                ("funct7", 31, 25),
+                ("rs2", 24, 20),
+                ("rs1", 19, 15),
+                ("funct3", 14, 12),
+                ("rd", 11, 7),
+                ("opcode", 6, 0),
+            ],
+            InstrFormat::I => &[
+                ("imm[11:0]", 31, 20),
+                ("rs1", 19, 15),
+                ("funct3", 14, 12),
+                ("rd", 11, 7),
+                ("opcode", 6, 0),
+            ],
+            InstrFormat::S => &[
+                ("imm[11:5]", 31, 25),
+                ("rs2", 24, 20),
+                ("rs1", 19, 15),
+                ("funct3", 14, 12),
+                ("imm[4:0]", 11, 7),
+                ("opcode", 6, 0),
+            ],
+            InstrFormat::B => &[
+                ("imm[12]", 31, 31),
+                ("imm[10:5]", 30, 25),
+                ("rs2", 24, 20),
+                ("rs1", 19, 15),
+                ("funct3", 14, 12),
+                ("imm[4:1]", 11, 8),
+                ("imm[11]", 7, 7),
+                ("opcode", 6, 0),
+            ],
+            InstrFormat::U => &[("imm[31:12]", 31, 12), ("rd", 11, 7), ("opcode", 6, 0)],
+            InstrFormat::J => &[
+                ("imm[20]", 31, 31),
+                ("imm[10:1]", 30, 21),
+                ("imm[11]", 20, 20),
+                ("imm[19:12]", 19, 12),
+                ("rd", 11, 7),
+                ("opcode", 6, 0),
+            ],
+            InstrFormat::R4 => &[
+                ("rs3", 31, 27),
+                ("funct2", 26, 25),
+                ("rs2", 24, 20),
+                ("rs1", 19, 15),
+                ("rm", 14, 12),
+                ("rd", 11, 7),
+                ("opcode", 6, 0),
+            ],
+            InstrFormat::Unknown => &[("unknown", 31, 7), ("opcode", 6, 0)],
+        }
+    }
+
+    /// Where the immediate bits come from: (width of the immediate,
+    /// pieces from the most significant bits).
+    /// Immediate bits not in the pieces are zero.
+    pub fn imm_layout(self) -> Option<(u32, &'static [ImmPiece])> {
+        match self {
+            InstrFormat::I => Some((12, &[(11, 0, 31)])),
+            InstrFormat::S => Some((12, &[(11, 5, 31), (4, 0, 11)])),
+            InstrFormat::B => Some((13, &[(12, 12, 31), (11, 11, 7), (10, 5, 30), (4, 1, 11)])),
+            InstrFormat::U => Some((32, &[(31, 12, 31)])),
+            InstrFormat::J => Some((21, &[(20, 20, 31), (19, 12, 19), (11, 11, 20), (10, 1, 30)])),
+            InstrFormat::R | InstrFormat::R4 | InstrFormat::Unknown => None,
+        }
+    }
+}
+
+pub mod opcode {
+    pub const LOAD: u32 = 0b000_0011;
+    pub const LOAD_FP: u32 = 0b000_0111;
+    pub const MISC_MEM: u32 = 0b000_1111;
+    pub const OP_IMM: u32 = 0b001_0011;
+    pub const AUIPC: u32 = 0b001_0111;
+    pub const OP_IMM_32: u32 = 0b001_1011;
+    pub const STORE: u32 = 0b010_0011;
+    pub const STORE_FP: u32 = 0b010_0111;
+    pub const AMO: u32 = 0b010_1111;
+    pub const OP: u32 = 0b011_0011;
+    pub const LUI: u32 = 0b011_0111;
+    pub const OP_32: u32 = 0b011_1011;
+    pub const BRANCH: u32 = 0b110_0011;
+    pub const JALR: u32 = 0b110_0111;
+    pub const JAL: u32 = 0b110_1111;
+    pub const SYSTEM: u32 = 0b111_0011;
+    pub const MADD: u32 = 0b100_0011;
+    pub const MSUB: u32 = 0b100_0111;
+    pub const NMSUB: u32 = 0b100_1011;
+    pub const NMADD: u32 = 0b100_1111;
+    pub const OP_FP: u32 = 0b101_0011;
+    pub const OP_V: u32 = 0b101_0111;
+    pub const CUSTOM_0: u32 = 0b000_1011;
+    pub const CUSTOM_1: u32 = 0b010_1011;
+    pub const CUSTOM_2: u32 = 0b101_1011;
+    pub const CUSTOM_3: u32 = 0b111_1011;
+
+    /// Name of the major opcode as in the ISA manual opcode map
+    pub fn name(opcode: u32) -> Option<&'static str> {
+        Some(match opcode {
+            LOAD => "LOAD",
+            LOAD_FP => "LOAD-FP",
+            MISC_MEM => "MISC-MEM",
+            OP_IMM => "OP-IMM",
+            AUIPC => "AUIPC",
+            OP_IMM_32 => "OP-IMM-32",
+            STORE => "STORE",
+            STORE_FP => "STORE-FP",
+            AMO => "AMO",
+            OP => "OP",
+            LUI => "LUI",
+            OP_32 => "OP-32",
+            BRANCH => "BRANCH",
+            JALR => "JALR",
+            JAL => "JAL",
+            SYSTEM => "SYSTEM",
+            MADD => "MADD",
+            MSUB => "MSUB",
+            NMSUB => "NMSUB",
+            NMADD => "NMADD",
+            OP_FP => "OP-FP",
+            OP_V => "OP-V",
+            CUSTOM_0 => "custom-0",
+            CUSTOM_1 => "custom-1",
+            CUSTOM_2 => "custom-2",
+            CUSTOM_3 => "custom-3",
+            _ => return None,
+        })
+    }
+}
+
+/// Fields of a decoded instruction, None if the format doesn't have the field
+pub struct InstrFields {
+    pub rd: Option<usize>,
+    pub rs1: Option<usize>,
+    pub rs2: Option<usize>,
+    pub rs3: Option<usize>,
+    pub funct3: Option<u32>,
+    pub funct7: Option<u32>,
+    /// Sign-extended immediate
+    pub imm: Option<i64>,
+}
+
+pub fn instr_format(instr: u32) -> InstrFormat {
+    match instr & 0x7f {
+        opcode::OP | opcode::OP_32 | opcode::AMO | opcode::OP_FP => InstrFormat::R,
+        opcode::LOAD
+        | opcode::LOAD_FP
+        | opcode::MISC_MEM
+        | opcode::OP_IMM
+        | opcode::OP_IMM_32
+        | opcode::JALR
+        | opcode::SYSTEM => InstrFormat::I,
+        opcode::STORE | opcode::STORE_FP => InstrFormat::S,
+        opcode::BRANCH => InstrFormat::B,
+        opcode::LUI | opcode::AUIPC => InstrFormat::U,
+        opcode::JAL => InstrFormat::J,
+        opcode::MADD | opcode::MSUB | opcode::NMSUB | opcode::NMADD => InstrFormat::R4,
+        _ => InstrFormat::Unknown,
+    }
+}
+
+/// Extract bits [hi:lo] of the instruction
+pub fn bits(instr: u32, hi: u32, lo: u32) -> u32 {
+    (instr >> lo) & (((1u64 << (hi - lo + 1)) - 1) as u32)
+}
+
+/// Sign-extend the lowest `width` bits
+pub fn sext(value: u32, width: u32) -> i64 {
+    let shift = 64 - width;
+    ((value as i64) << shift) >> shift
+}
+
+pub fn decode_fields(instr: u32) -> InstrFields {
+    let format = instr_format(instr);
+    let rd = Some(bits(instr, 11, 7) as usize);
+    let rs1 = Some(bits(instr, 19, 15) as usize);
+    let rs2 = Some(bits(instr, 24, 20) as usize);
+    let funct3 = Some(bits(instr, 14, 12));
+    let funct7 = Some(bits(instr, 31, 25));
+    let mut fields = InstrFields {
+        rd: None,
+        rs1: None,
+        rs2: None,
+        rs3: None,
+        funct3: None,
+        funct7: None,
+        imm: None,
+    };
+    match format {
+        InstrFormat::R => {
+            fields.rd = rd;
+            fields.rs1 = rs1;
+            fields.rs2 = rs2;
+            fields.funct3 = funct3;
+            fields.funct7 = funct7;
+        }
+        InstrFormat::I => {
+            fields.rd = rd;
+            fields.rs1 = rs1;
+            fields.funct3 = funct3;
+            fields.imm = Some(sext(bits(instr, 31, 20), 12));
+        }
+        InstrFormat::S => {
+            fields.rs1 = rs1;
+            fields.rs2 = rs2;
+            fields.funct3 = funct3;
+            fields.imm = Some(sext(bits(instr, 31, 25) << 5 | bits(instr, 11, 7), 12));
+        }
+        InstrFormat::B => {
+            fields.rs1 = rs1;
+            fields.rs2 = rs2;
+            fields.funct3 = funct3;
+            let imm = bits(instr, 31, 31) << 12
+                | bits(instr, 7, 7) << 11
+                | bits(instr, 30, 25) << 5
+                | bits(instr, 11, 8) << 1;
+            fields.imm = Some(sext(imm, 13));
+        }
+        InstrFormat::U => {
+            fields.rd = rd;
+            fields.imm = Some(sext(instr & 0xffff_f000, 32));
+        }
+        InstrFormat::J => {
+            fields.rd = rd;
+            let imm = bits(instr, 31, 31) << 20
+                | bits(instr, 19, 12) << 12
+                | bits(instr, 20, 20) << 11
+                | bits(instr, 30, 21) << 1;
+            fields.imm = Some(sext(imm, 21));
+        }
+        InstrFormat::R4 => {
+            fields.rd = rd;
+            fields.rs1 = rs1;
+            fields.rs2 = rs2;
+            fields.rs3 = Some(bits(instr, 31, 27) as usize);
+            fields.funct3 = funct3;
+        }
+        InstrFormat::Unknown => {}
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bit_ranges() {
+        assert_eq!(bits(0x0015_0513, 6, 0), 0x13);
+        assert_eq!(bits(0x0015_0513, 31, 20), 1);
+        assert_eq!(bits(0x8000_0000, 31, 31), 1);
+        assert_eq!(bits(0xdead_beef, 31, 0), 0xdead_beef);
+        assert_eq!(bits(0xdead_beef, 31, 1), 0xdead_beef >> 1);
+    }
+
+    #[test]
+    fn sign_extension() {
+        assert_eq!(sext(0xfff, 12), -1);
+        assert_eq!(sext(0x7ff, 12), 0x7ff);
+        assert_eq!(sext(0xffff_ffff, 32), -1);
+    }
+}
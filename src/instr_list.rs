@@ -1,9 +1,161 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use kompusim::rv64i_disasm::disasm;
+
+use crate::{
+    annotations::{AnnotationKind, Annotations},
+    branch_arrows::{branch_target, draw_arrows, layout_arrows, Arrow, Branch},
+    breakpoints::{Breakpoints, Condition},
+    elf::{Section, Symbol},
+    instr_format::decode_fields,
+    instr_search::{SearchQuery, SEARCH_HELP},
+    isa::{branch_taken, diagnose, lookup, preview},
+    listing::{listing_data_line, listing_header, listing_line, ExportAction},
+    number::parse_addr,
+    regions::{data_directive, data_row_len, RegionKind, Regions, MAX_STRING_LEN},
+    regs::ABI_NAMES,
+    rvc::{decode_compressed, expand, instr_len, is_compressed},
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::listing::save_listing;
+
+/// Memory is fetched from the simulator in windows of this size (bytes)
+const MEM_WINDOW_SIZE: u64 = 1024;
+
+/// Active search and its results
+struct Search {
+    query: SearchQuery,
+    /// Addresses of matching instructions
+    matches: Vec<u64>,
+    /// Index of the current match
+    current: usize,
+    /// Results have to be recomputed (memory changed)
+    dirty: bool,
+}
+
+/// Memory window received from the simulator and the table rows starting in it
+struct MemWindow {
+    data: Vec<u8>,
+    /// Addresses of the rows starting in the window
+    rows: Vec<u64>,
+    /// Address of the first row after the window, the last row can end in the next window
+    end: u64,
+    /// The program could have changed the memory since it was received
+    stale: bool,
+}
+
+/// Label or comment being edited in the table
+struct Editing {
+    addr: u64,
+    kind: AnnotationKind,
+    text: String,
+    /// Focus the text field on the first frame
+    focus: bool,
+    error: Option<String>,
+}
+
+/// Content of a table row
+enum RowContent {
+    Instr(u32),
+    Data(RegionKind, Vec<u8>),
+}
+
+/// State of the window for marking regions
+struct RegionEditor {
+    /// Inclusive range: label, hex address or "pc"
+    from_text: String,
+    to_text: String,
+    kind: RegionKind,
+    error: Option<String>,
+}
+
+/// State of the export window
+struct Export {
+    whole_image: bool,
+    /// Inclusive range: label, hex address or "pc"
+    from_text: String,
+    to_text: String,
+    /// Action waiting for the memory to be fetched
+    pending: Option<ExportAction>,
+    status: Option<String>,
+}
+
 #[derive(serde::Deserialize, serde::Serialize)]
 #[serde(default)]
 pub struct InstrList {
     /// Is window open or not
     open: bool,
     font_size: usize,
+    /// Scroll to the current PC when the simulator stops
+    follow_pc: bool,
+    /// User labels and comments, key is the image name
+    annotations: BTreeMap<String, Annotations>,
+    #[serde(skip)]
+    image_name: String,
+    /// Symbols of the loaded ELF file
+    #[serde(skip)]
+    symbols: BTreeMap<u64, String>,
+    /// Data regions of the loaded ELF file
+    #[serde(skip)]
+    elf_regions: Regions,
+    /// Region editor window is open if Some
+    #[serde(skip)]
+    region_editor: Option<RegionEditor>,
+    #[serde(skip)]
+    editing: Option<Editing>,
+    #[serde(skip)]
+    pc: Option<u64>,
+    /// Integer registers while the simulator is stopped at the PC
+    #[serde(skip)]
+    regs: Option<[u64; 32]>,
+    /// PC before the last stop
+    #[serde(skip)]
+    prev_pc: Option<u64>,
+    /// Branch executed by the last step: (address, taken)
+    #[serde(skip)]
+    last_branch: Option<(u64, bool)>,
+    /// First and last table rows shown in the previous frame
+    #[serde(skip)]
+    visible_rows: Option<(usize, usize)>,
+    /// Scroll to the instruction at the address on the next frame
+    #[serde(skip)]
+    scroll_to: Option<u64>,
+    #[serde(skip)]
+    goto_text: String,
+    #[serde(skip)]
+    goto_error: Option<String>,
+    #[serde(skip)]
+    search_text: String,
+    #[serde(skip)]
+    search: Option<Search>,
+    /// Export window is open if Some
+    #[serde(skip)]
+    export: Option<Export>,
+    /// Address range of the loaded image: [start, end)
+    #[serde(skip)]
+    range: Option<(u64, u64)>,
+    /// Index of the first table row of each memory window. Rows of fetched windows
+    /// are instructions (2 or 4 bytes) and data, the rest is shown as 4-byte rows.
+    #[serde(skip)]
+    window_first_rows: Vec<usize>,
+    #[serde(skip)]
+    num_rows: usize,
+    /// Row numbers have to be recomputed (rows of a window changed)
+    #[serde(skip)]
+    rows_dirty: bool,
+    /// Memory windows received from the simulator, key is the window address
+    #[serde(skip)]
+    mem_windows: HashMap<u64, MemWindow>,
+    /// Windows requested but not received yet
+    #[serde(skip)]
+    requested_windows: HashSet<u64>,
+    /// Windows (address, length) to request from the simulator
+    #[serde(skip)]
+    pending_requests: Vec<(u64, u64)>,
+    /// Instruction to open in the decoder: (instruction, address)
+    #[serde(skip)]
+    decode_request: Option<(u32, u64)>,
 }
 
 impl Default for InstrList {
@@ -11,6 +163,32 @@ impl Default for InstrList {
         InstrList {
             open: true,
             font_size: 0,
+            follow_pc: true,
+            annotations: BTreeMap::new(),
+            image_name: String::new(),
+            symbols: BTreeMap::new(),
+            elf_regions: Regions::default(),
+            region_editor: None,
+            editing: None,
+            pc: None,
+            regs: None,
+            prev_pc: None,
+            last_branch: None,
+            visible_rows: None,
+            scroll_to: None,
+            goto_text: String::new(),
+            goto_error: None,
+            search_text: String::new(),
+            search: None,
+            export: None,
+            range: None,
+            window_first_rows: Vec::new(),
+            num_rows: 0,
+            rows_dirty: false,
+            mem_windows: HashMap::new(),
+            requested_windows: HashSet::new(),
+            pending_requests: Vec::new(),
+            decode_request: None,
         }
     }
 }
@@ -19,41 +197,1053 @@ impl InstrList {
     pub fn open(&mut self) {
         self.open = true;
     }
-    pub fn show(&mut self, ctx: &egui::Context) {
+
+    /// Show instructions of a newly loaded image
+    pub fn set_image(&mut self, name: &str, addr: u64, len: u64) {
+        self.image_name = name.to_string();
+        self.symbols.clear();
+        self.elf_regions = Regions::default();
+        self.editing = None;
+        self.range = Some((addr, addr + len));
+        self.rows_dirty = true;
+        self.pc = None;
+        self.regs = None;
+        self.prev_pc = None;
+        self.last_branch = None;
+        self.mem_windows.clear();
+        self.requested_windows.clear();
+        self.pending_requests.clear();
+    }
+
+    /// Symbols and data regions from the sections of the loaded ELF file
+    pub fn set_elf_info(&mut self, sections: &[Section], symbols: &[Symbol]) {
+        self.elf_regions = Regions::from_sections(sections);
+        self.symbols = symbols
+            .iter()
+            .map(|symbol| (symbol.addr, symbol.name.clone()))
+            .collect();
+        self.update_all_window_rows();
+    }
+
+    /// The program could have modified the memory: fetch the windows around the shown rows
+    /// and the PC again, other windows are fetched when they are shown.
+    /// Old content is shown until the new one arrives.
+    pub fn refresh(&mut self) {
+        for window in self.mem_windows.values_mut() {
+            window.stale = true;
+        }
+        self.request_visible_windows();
+    }
+
+    /// The simulator stopped at the PC, `stepped` - after a single step,
+    /// `x` - integer registers
+    pub fn sim_stopped(&mut self, pc: u64, stepped: bool, x: [u64; 32]) {
+        self.last_branch = match self.pc {
+            Some(old_pc) if stepped => self
+                .instr_at(old_pc)
+                .and_then(|instr| self.branch_outcome(instr, old_pc, pc)),
+            _ => None,
+        };
+        if self.pc != Some(pc) {
+            self.prev_pc = self.pc;
+        }
+        self.pc = Some(pc);
+        self.regs = Some(x);
+        if self.follow_pc {
+            self.scroll_to = Some(pc);
+        }
+        self.refresh();
+        self.request_window(pc);
+    }
+
+    /// (address, taken) of the branch at `old_pc` which continued at `pc`
+    fn branch_outcome(&self, instr: u32, old_pc: u64, pc: u64) -> Option<(u64, bool)> {
+        let instr32 = expand(instr);
+        let (target, conditional) = branch_target(instr32, old_pc)?;
+        if target != old_pc.wrapping_add(instr_len(instr)) || !conditional {
+            return Some((old_pc, target == pc));
+        }
+        // both outcomes continue at the next instruction, the condition
+        // is evaluated on the registers before the step
+        let def = lookup(instr32)?;
+        let fields = decode_fields(instr32);
+        let x = self.regs.as_ref()?;
+        Some((old_pc, branch_taken(def, x[fields.rs1?], x[fields.rs2?])))
+    }
+
+    /// Register values are stale while the simulator runs
+    pub fn sim_running(&mut self) {
+        self.regs = None;
+    }
+
+    /// Table row of the instruction containing the address
+    fn addr_to_row(&self, addr: u64) -> Option<usize> {
+        let (start, end) = self.range?;
+        if !(start..end).contains(&addr) {
+            return None;
+        }
+        let index = ((addr - start) / MEM_WINDOW_SIZE) as usize;
+        let first_row = *self.window_first_rows.get(index)?;
+        let window_addr = start + index as u64 * MEM_WINDOW_SIZE;
+        let first_addr = self.window_first_addr(window_addr);
+        if addr < first_addr {
+            // in the last row of the previous window
+            return first_row.checked_sub(1);
+        }
+        Some(match self.mem_windows.get(&window_addr) {
+            Some(window) => first_row + window.rows.partition_point(|row| *row <= addr) - 1,
+            None => first_row + ((addr - first_addr) / 4) as usize,
+        })
+    }
+
+    /// Address of the table row
+    fn row_addr(&self, row: usize) -> Option<u64> {
+        let (start, _) = self.range?;
+        if row >= self.num_rows {
+            return None;
+        }
+        let index = self
+            .window_first_rows
+            .partition_point(|first| *first <= row)
+            - 1;
+        let window_addr = start + index as u64 * MEM_WINDOW_SIZE;
+        let i = row - self.window_first_rows[index];
+        match self.mem_windows.get(&window_addr) {
+            Some(window) => window.rows.get(i).copied(),
+            None => Some(self.window_first_addr(window_addr) + 4 * i as u64),
+        }
+    }
+
+    /// Address of the first row of the window, the last row of the previous window
+    /// can continue in it
+    fn window_first_addr(&self, window_addr: u64) -> u64 {
+        match window_addr.checked_sub(MEM_WINDOW_SIZE) {
+            Some(prev) if self.range.is_some_and(|(start, _)| window_addr > start) => self
+                .mem_windows
+                .get(&prev)
+                .map_or(window_addr, |window| window.end),
+            _ => window_addr,
+        }
+    }
+
+    /// Kind of the region containing the address and the end of the region.
+    /// Regions marked by the user override ELF regions, the rest is code.
+    fn region_at(&self, addr: u64) -> (RegionKind, u64) {
+        let image_end = self.range.map_or(addr, |(_, end)| end);
+        let user_regions = self
+            .annotations
+            .get(&self.image_name)
+            .map(|annotations| &annotations.regions);
+        if let Some(region) = user_regions.and_then(|regions| regions.find(addr)) {
+            return (region.kind, region.end.min(image_end));
+        }
+        let next_user_start = user_regions
+            .and_then(|regions| regions.next_start(addr))
+            .unwrap_or(image_end);
+        if let Some(region) = self.elf_regions.find(addr) {
+            return (region.kind, region.end.min(next_user_start).min(image_end));
+        }
+        let next_start = self
+            .elf_regions
+            .next_start(addr)
+            .unwrap_or(image_end)
+            .min(next_user_start)
+            .min(image_end);
+        (RegionKind::Code, next_start)
+    }
+
+    /// Number the rows of the windows
+    fn update_rows(&mut self) {
+        if !self.rows_dirty {
+            return;
+        }
+        self.rows_dirty = false;
+        let (start, end) = match self.range {
+            Some(range) => range,
+            None => return,
+        };
+        self.window_first_rows.clear();
+        self.num_rows = 0;
+        for window_addr in (start..end).step_by(MEM_WINDOW_SIZE as usize) {
+            self.window_first_rows.push(self.num_rows);
+            self.num_rows += match self.mem_windows.get(&window_addr) {
+                Some(window) => window.rows.len(),
+                None => {
+                    let window_end = (window_addr + MEM_WINDOW_SIZE).min(end);
+                    let first_addr = self.window_first_addr(window_addr);
+                    window_end.saturating_sub(first_addr).div_ceil(4) as usize
+                }
+            };
+        }
+    }
+
+    /// Split the fetched window into instructions (decoding their lengths) and data rows.
+    /// Returns true if the end of its last row moved.
+    fn update_window_rows(&mut self, window_addr: u64) -> bool {
+        let image_end = match self.range {
+            Some((_, end)) => end,
+            None => return false,
+        };
+        if !self.mem_windows.contains_key(&window_addr) {
+            return false;
+        }
+        let window_end = (window_addr + MEM_WINDOW_SIZE).min(image_end);
+        let mut rows = Vec::new();
+        let mut addr = self.window_first_addr(window_addr);
+        while addr < window_end {
+            rows.push(addr);
+            let (kind, region_end) = self.region_at(addr);
+            let len = if kind == RegionKind::Code {
+                self.fetched_parcel(addr)
+                    .map(|parcel| instr_len(parcel as u32))
+            } else {
+                let len = (region_end - addr).min(MAX_STRING_LEN);
+                self.fetched_bytes(addr, len)
+                    .map(|bytes| data_row_len(kind, &bytes))
+            };
+            // the rest of the row is fetched with the next window
+            addr += len.unwrap_or(4).min(region_end - addr).max(1);
+        }
+        self.rows_dirty = true;
+        let window = self.mem_windows.get_mut(&window_addr).unwrap();
+        window.rows = rows;
+        std::mem::replace(&mut window.end, addr) != addr
+    }
+
+    /// Rows of all fetched windows, e.g., after the regions changed
+    fn update_all_window_rows(&mut self) {
+        let mut window_addrs: Vec<u64> = self.mem_windows.keys().copied().collect();
+        window_addrs.sort_unstable();
+        for window_addr in window_addrs {
+            self.update_window_rows(window_addr);
+        }
+        self.rows_dirty = true;
+    }
+
+    /// Content of the row if its memory is fetched
+    fn row_content(&mut self, row: usize) -> Option<RowContent> {
+        let addr = self.row_addr(row)?;
+        let (kind, region_end) = self.region_at(addr);
+        if kind == RegionKind::Code {
+            return self.instr_at(addr).map(RowContent::Instr);
+        }
+        let next_row = self.row_addr(row + 1).unwrap_or(region_end);
+        let bytes = self.bytes_at(addr, next_row.min(region_end) - addr)?;
+        Some(RowContent::Data(kind, bytes))
+    }
+
+    /// User label or symbol at the address
+    pub fn label_at(&self, addr: u64) -> Option<&str> {
+        self.annotations
+            .get(&self.image_name)
+            .and_then(|annotations| annotations.get(AnnotationKind::Label, addr))
+            .or_else(|| self.symbols.get(&addr).map(String::as_str))
+    }
+
+    /// User regions changed, rows have to be recomputed
+    fn regions_changed(&mut self) {
+        self.update_all_window_rows();
+        if let Some(search) = &mut self.search {
+            search.dirty = true;
+        }
+    }
+
+    /// Memory content received from the simulator
+    pub fn memory_received(&mut self, addr: u64, data: Vec<u8>) {
+        if !self.requested_windows.remove(&addr) {
+            return;
+        }
+        let end = self.window_first_addr(addr);
+        self.mem_windows.insert(
+            addr,
+            MemWindow {
+                data,
+                rows: Vec::new(),
+                end,
+                stale: false,
+            },
+        );
+        // the last row of the previous window can continue in this one,
+        // rows of the next windows move if the last row of this one changes
+        if let Some(prev) = addr.checked_sub(MEM_WINDOW_SIZE) {
+            self.update_window_rows(prev);
+        }
+        let mut window_addr = addr;
+        while self.update_window_rows(window_addr) {
+            window_addr += MEM_WINDOW_SIZE;
+        }
+        if let Some(search) = &mut self.search {
+            search.dirty = true;
+        }
+    }
+
+    /// Returns (instruction, address) to show in the instruction decoder
+    pub fn take_decode_request(&mut self) -> Option<(u32, u64)> {
+        self.decode_request.take()
+    }
+
+    /// Returns (address, length) of memory windows to request from the simulator
+    pub fn take_mem_requests(&mut self) -> Vec<(u64, u64)> {
+        std::mem::take(&mut self.pending_requests)
+    }
+
+    /// Schedule fetching of the window containing the address
+    /// unless it is fetched and up to date
+    fn request_window(&mut self, addr: u64) {
+        let (start, end) = match self.range {
+            Some(range) if (range.0..range.1).contains(&addr) => range,
+            _ => return,
+        };
+        // windows never cross the image boundaries
+        let window_addr = start + (addr - start) / MEM_WINDOW_SIZE * MEM_WINDOW_SIZE;
+        let up_to_date = self
+            .mem_windows
+            .get(&window_addr)
+            .is_some_and(|window| !window.stale);
+        if !up_to_date && self.requested_windows.insert(window_addr) {
+            let len = MEM_WINDOW_SIZE.min(end - window_addr);
+            self.pending_requests.push((window_addr, len));
+        }
+    }
+
+    /// Schedule fetching of the windows with the rows shown in the previous frame,
+    /// and the windows before and after them
+    fn request_visible_windows(&mut self) {
+        let (first, last) = match self.visible_rows {
+            Some(rows) => rows,
+            None => return,
+        };
+        let (first, last) = match (self.row_addr(first), self.row_addr(last)) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return,
+        };
+        let mut addr = first.saturating_sub(MEM_WINDOW_SIZE);
+        while addr <= last.saturating_add(MEM_WINDOW_SIZE) {
+            self.request_window(addr);
+            addr += MEM_WINDOW_SIZE;
+        }
+    }
+
+    /// Byte at the address if its memory is fetched, doesn't schedule fetching
+    fn fetched_byte(&self, addr: u64) -> Option<u8> {
+        let (start, end) = self.range?;
+        if !(start..end).contains(&addr) {
+            return None;
+        }
+        let window_addr = start + (addr - start) / MEM_WINDOW_SIZE * MEM_WINDOW_SIZE;
+        let window = self.mem_windows.get(&window_addr)?;
+        window.data.get((addr - window_addr) as usize).copied()
+    }
+
+    fn fetched_bytes(&self, addr: u64, len: u64) -> Option<Vec<u8>> {
+        (addr..addr + len)
+            .map(|addr| self.fetched_byte(addr))
+            .collect()
+    }
+
+    fn fetched_parcel(&self, addr: u64) -> Option<u16> {
+        Some(u16::from_le_bytes([
+            self.fetched_byte(addr)?,
+            self.fetched_byte(addr + 1)?,
+        ]))
+    }
+
+    /// Returns the byte at the address if its memory is fetched,
+    /// otherwise schedules fetching. Stale memory is fetched again.
+    fn byte_at(&mut self, addr: u64) -> Option<u8> {
+        self.request_window(addr);
+        self.fetched_byte(addr)
+    }
+
+    /// Memory [addr, addr + len) if it is fetched
+    fn bytes_at(&mut self, addr: u64, len: u64) -> Option<Vec<u8>> {
+        let mut bytes = Vec::with_capacity(len as usize);
+        let mut complete = true;
+        // request all missing windows at once
+        for byte_addr in addr..addr + len {
+            match self.byte_at(byte_addr) {
+                Some(byte) => bytes.push(byte),
+                None => complete = false,
+            }
+        }
+        complete.then_some(bytes)
+    }
+
+    /// 16-bit parcel at the address if its memory is fetched
+    fn parcel_at(&mut self, addr: u64) -> Option<u16> {
+        let low = self.byte_at(addr)?;
+        let high = self.byte_at(addr + 1)?;
+        Some(u16::from_le_bytes([low, high]))
+    }
+
+    /// Returns the instruction at the address, 16-bit instructions are not expanded.
+    /// A 32-bit instruction can span two memory windows.
+    fn instr_at(&mut self, addr: u64) -> Option<u32> {
+        let low = self.parcel_at(addr)? as u32;
+        if is_compressed(low) {
+            return Some(low);
+        }
+        let high = self.parcel_at(addr + 2)? as u32;
+        Some(high << 16 | low)
+    }
+
+    /// Returns all rows of the image or None if some memory is not fetched yet.
+    /// Fetches the whole image, for search and export.
+    fn all_rows(&mut self) -> Option<Vec<(u64, RowContent)>> {
+        self.update_rows();
+        let mut rows = Vec::with_capacity(self.num_rows);
+        let mut complete = true;
+        // walk the whole range to request all missing windows at once
+        for row in 0..self.num_rows {
+            match (self.row_addr(row), self.row_content(row)) {
+                (Some(addr), Some(content)) => rows.push((addr, content)),
+                _ => complete = false,
+            }
+        }
+        complete.then_some(rows)
+    }
+
+    /// Address of a label, a hex address or "pc"
+    fn resolve_addr(&self, text: &str) -> Result<u64, String> {
+        let text = text.trim();
+        let label_addr = self
+            .annotations
+            .get(&self.image_name)
+            .and_then(|annotations| annotations.find_label(text))
+            .or_else(|| {
+                self.symbols
+                    .iter()
+                    .find(|(_, name)| *name == text)
+                    .map(|(addr, _)| *addr)
+            });
+        let addr = if let Some(addr) = label_addr {
+            Ok(addr)
+        } else if text.eq_ignore_ascii_case("pc") {
+            self.pc.ok_or("the simulator has no PC yet")
+        } else {
+            parse_addr(text).ok_or("expected label, hex address or \"pc\"")
+        };
+        match addr {
+            Ok(addr) if self.addr_to_row(addr).is_some() => Ok(addr),
+            Ok(_) => Err("the address is outside of the loaded image".to_string()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    /// Jump to a label, an address (hex) or "pc"
+    fn goto(&mut self) {
+        self.goto_error = match self.resolve_addr(&self.goto_text) {
+            Ok(addr) => {
+                self.scroll_to = Some(addr);
+                None
+            }
+            Err(err) => Some(err),
+        };
+    }
+
+    /// Disassembly of the instructions in [from, to] in objdump-like text,
+    /// None if some memory is not fetched yet
+    fn listing(&mut self, from: u64, to: u64) -> Option<String> {
+        let rows = self.all_rows()?;
+        let annotations = self.annotations.get(&self.image_name);
+        let mut text = listing_header(&self.image_name);
+        for (addr, content) in rows {
+            if (from..=to).contains(&addr) {
+                let label = self.label_at(addr);
+                let comment = annotations.and_then(|a| a.get(AnnotationKind::Comment, addr));
+                match content {
+                    RowContent::Instr(instr) => {
+                        listing_line(&mut text, addr, instr, label, comment)
+                    }
+                    RowContent::Data(kind, bytes) => {
+                        listing_data_line(&mut text, addr, kind, &bytes, label, comment)
+                    }
+                }
+            }
+        }
+        Some(text)
+    }
+
+    /// Run the export action once the memory of the image is fetched
+    fn run_export(&mut self, ctx: &egui::Context) {
+        let export = match &self.export {
+            Some(export) => export,
+            None => return,
+        };
+        let action = match export.pending {
+            Some(action) => action,
+            None => return,
+        };
+        let range = if export.whole_image {
+            self.range
+                .map(|(start, end)| (start, end - 1))
+                .ok_or("no program loaded".to_string())
+        } else {
+            self.resolve_addr(&export.from_text)
+                .and_then(|from| Ok((from, self.resolve_addr(&export.to_text)?)))
+        };
+        let status = match range {
+            Ok((from, to)) => match self.listing(from, to) {
+                Some(text) => match action {
+                    #[cfg(not(target_arch = "wasm32"))]
+                    ExportAction::Save => save_listing(&self.image_name, &text),
+                    ExportAction::Copy => {
+                        ctx.output_mut(|o| o.copied_text = text);
+                        Some("copied to the clipboard".to_string())
+                    }
+                },
+                None => {
+                    // all memory windows are requested, try again on the next frame
+                    ctx.request_repaint();
+                    return;
+                }
+            },
+            Err(err) => Some(err),
+        };
+        if let Some(export) = &mut self.export {
+            export.pending = None;
+            export.status = status;
+        }
+    }
+
+    fn open_region_editor(&mut self, from: u64) {
+        let editor = self.region_editor.get_or_insert(RegionEditor {
+            from_text: String::new(),
+            to_text: String::new(),
+            kind: RegionKind::Words,
+            error: None,
+        });
+        editor.from_text = format!("{from:x}");
+        editor.error = None;
+    }
+
+    /// Mark the range from the editor, `to` is the address of the last row
+    fn mark_region(&mut self) {
+        let editor = match &self.region_editor {
+            Some(editor) => editor,
+            None => return,
+        };
+        let kind = editor.kind;
+        let range = self.resolve_addr(&editor.from_text).and_then(|from| {
+            let to = self.resolve_addr(&editor.to_text)?;
+            let to_row = self.addr_to_row(to).unwrap_or_default();
+            let end = match (self.row_addr(to_row + 1), self.range) {
+                (Some(next_row), _) => next_row,
+                (None, Some((_, image_end))) => image_end,
+                (None, None) => to + 1,
+            };
+            if from < end {
+                Ok((from, end))
+            } else {
+                Err("the range is empty".to_string())
+            }
+        });
+        let error = match range {
+            Ok((start, end)) => {
+                self.annotations
+                    .entry(self.image_name.clone())
+                    .or_default()
+                    .regions
+                    .set(start, end, kind);
+                self.regions_changed();
+                None
+            }
+            Err(err) => Some(err),
+        };
+        if let Some(editor) = &mut self.region_editor {
+            editor.error = error;
+        }
+    }
+
+    fn show_region_editor(&mut self, ctx: &egui::Context) {
+        let mut open = self.region_editor.is_some();
+        let mut mark = false;
+        let mut remove = None;
+        egui::Window::new("Code and data regions")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let editor = self.region_editor.as_mut().unwrap();
+                egui::Grid::new("region_editor_grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("From");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut editor.from_text)
+                                .hint_text("label, address or pc")
+                                .desired_width(120.0),
+                        );
+                        ui.end_row();
+                        ui.label("To (inclusive)");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut editor.to_text)
+                                .hint_text("label, address or pc")
+                                .desired_width(120.0),
+                        );
+                        ui.end_row();
+                        ui.label("Kind");
+                        egui::ComboBox::from_id_source("region_kind")
+                            .selected_text(editor.kind.name())
+                            .show_ui(ui, |ui| {
+                                for kind in RegionKind::ALL {
+                                    ui.selectable_value(&mut editor.kind, kind, kind.name());
+                                }
+                            });
+                        ui.end_row();
+                    });
+                ui.horizontal(|ui| {
+                    mark = ui.button("Mark").clicked();
+                    if let Some(err) = &editor.error {
+                        ui.colored_label(ui.visuals().error_fg_color, err);
+                    }
+                });
+                ui.separator();
+                ui.strong("Marked regions");
+                let user_regions = self
+                    .annotations
+                    .get(&self.image_name)
+                    .map(|annotations| annotations.regions.list())
+                    .unwrap_or_default();
+                if user_regions.is_empty() {
+                    ui.label("none");
+                }
+                for (i, region) in user_regions.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.monospace(format!("{:x}..{:x}", region.start, region.end));
+                        ui.label(region.kind.name());
+                        if ui.small_button("✖").on_hover_text("Remove").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                }
+                if !self.elf_regions.list().is_empty() {
+                    ui.separator();
+                    ui.strong("From ELF sections");
+                    for region in self.elf_regions.list() {
+                        ui.horizontal(|ui| {
+                            ui.monospace(format!("{:x}..{:x}", region.start, region.end));
+                            ui.label(region.kind.name());
+                        });
+                    }
+                }
+            });
+        if mark {
+            self.mark_region();
+        }
+        if let Some(i) = remove {
+            if let Some(annotations) = self.annotations.get_mut(&self.image_name) {
+                annotations.regions.remove(i);
+                self.regions_changed();
+            }
+        }
+        if !open {
+            self.region_editor = None;
+        }
+    }
+
+    fn show_export_window(&mut self, ctx: &egui::Context) {
+        let export = match &mut self.export {
+            Some(export) => export,
+            None => return,
+        };
+        let mut open = true;
+        egui::Window::new("Export disassembly")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.radio_value(&mut export.whole_image, true, "Whole image");
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut export.whole_image, false, "Range");
+                    ui.add_enabled_ui(!export.whole_image, |ui| {
+                        ui.add(
+                            egui::TextEdit::singleline(&mut export.from_text)
+                                .hint_text("from")
+                                .desired_width(120.0),
+                        );
+                        ui.add(
+                            egui::TextEdit::singleline(&mut export.to_text)
+                                .hint_text("to (inclusive)")
+                                .desired_width(120.0),
+                        );
+                    });
+                });
+                ui.horizontal(|ui| {
+                    ui.add_enabled_ui(export.pending.is_none(), |ui| {
+                        #[cfg(not(target_arch = "wasm32"))]
+                        if ui.button("Save...").clicked() {
+                            export.pending = Some(ExportAction::Save);
+                        }
+                        if ui.button("Copy to clipboard").clicked() {
+                            export.pending = Some(ExportAction::Copy);
+                        }
+                    });
+                    if export.pending.is_some() {
+                        ui.label("fetching memory...");
+                    } else if let Some(status) = &export.status {
+                        ui.label(status);
+                    }
+                });
+            });
+        if !open {
+            self.export = None;
+        }
+        self.run_export(ctx);
+    }
+
+    fn start_search(&mut self) {
+        self.search = match SearchQuery::parse(&self.search_text) {
+            Ok(query) => Some(Search {
+                query,
+                matches: Vec::new(),
+                current: 0,
+                dirty: true,
+            }),
+            Err(_) => None,
+        };
+    }
+
+    /// Recompute search results once all memory of the image is fetched
+    fn update_search(&mut self) {
+        if !self.search.as_ref().is_some_and(|search| search.dirty) {
+            return;
+        }
+        let instrs: Vec<(u64, u32)> = match self.all_rows() {
+            Some(rows) => rows
+                .into_iter()
+                .filter_map(|(addr, content)| match content {
+                    RowContent::Instr(instr) => Some((addr, instr)),
+                    RowContent::Data(..) => None,
+                })
+                .collect(),
+            None => return,
+        };
+        let annotations = self.annotations.get(&self.image_name);
+        let search = self.search.as_mut().unwrap();
+        search.dirty = false;
+        let first_search = search.matches.is_empty();
+        search.matches = instrs
+            .into_iter()
+            .filter(|(addr, instr)| {
+                let notes = annotations
+                    .map(|annotations| annotations.search_text(*addr))
+                    .unwrap_or_default();
+                let instr = expand(*instr);
+                search.query.matches(instr, &disasm(instr, *addr), &notes)
+            })
+            .map(|(addr, _)| addr)
+            .collect();
+        search.current = search.current.min(search.matches.len().saturating_sub(1));
+        if first_search {
+            self.scroll_to = search.matches.first().copied();
+        }
+    }
+
+    /// Move to the next (forward) or previous match
+    fn next_match(&mut self, forward: bool) {
+        if let Some(search) = &mut self.search {
+            let n = search.matches.len();
+            if n > 0 {
+                search.current = if forward {
+                    (search.current + 1) % n
+                } else {
+                    (search.current + n - 1) % n
+                };
+                self.scroll_to = Some(search.matches[search.current]);
+            }
+        }
+    }
+
+    /// Arrows of branches with both ends in the rows shown in the previous frame
+    fn visible_arrows(&mut self) -> Vec<Arrow> {
+        let (first, last) = match self.visible_rows {
+            Some(rows) => rows,
+            None => return Vec::new(),
+        };
+        let mut branches = Vec::new();
+        for row in first..=last.min(self.num_rows.saturating_sub(1)) {
+            let addr = match self.row_addr(row) {
+                Some(addr) => addr,
+                None => continue,
+            };
+            let target = match self.row_content(row) {
+                Some(RowContent::Instr(instr)) => branch_target(expand(instr), addr),
+                _ => None,
+            };
+            let (target, conditional) = match target {
+                Some(target) => target,
+                None => continue,
+            };
+            if let Some(to_row) = self
+                .addr_to_row(target)
+                .filter(|to_row| (first..=last).contains(to_row))
+            {
+                branches.push(Branch {
+                    from_row: row,
+                    to_row,
+                    conditional,
+                    is_current: self.pc == Some(addr),
+                    taken: self
+                        .last_branch
+                        .filter(|(branch_addr, _)| *branch_addr == addr)
+                        .map(|(_, taken)| taken),
+                });
+            }
+        }
+        layout_arrows(branches)
+    }
+
+    /// Label or comment of the instruction, double-click edits it
+    fn annotation_cell(&mut self, ui: &mut egui::Ui, addr: u64, kind: AnnotationKind) {
+        match &mut self.editing {
+            Some(editing) if editing.addr == addr && editing.kind == kind => {
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut editing.text).desired_width(f32::INFINITY),
+                );
+                if editing.focus {
+                    response.request_focus();
+                    editing.focus = false;
+                }
+                if let Some(err) = &editing.error {
+                    let color = ui.visuals().error_fg_color;
+                    egui::show_tooltip_for(ui.ctx(), response.id, &response.rect, |ui| {
+                        ui.colored_label(color, err);
+                    });
+                }
+                if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                    self.editing = None;
+                } else if response.lost_focus() {
+                    self.finish_editing();
+                }
+            }
+            _ => {
+                let id = ui.id().with((addr, kind == AnnotationKind::Label));
+                let response = ui
+                    .interact(ui.max_rect(), id, egui::Sense::click())
+                    .on_hover_text("Double-click to edit");
+                let text = self
+                    .annotations
+                    .get(&self.image_name)
+                    .and_then(|annotations| annotations.get(kind, addr));
+                if let Some(text) = text {
+                    let text = match kind {
+                        AnnotationKind::Label => {
+                            egui::RichText::new(format!("{text}:")).monospace().strong()
+                        }
+                        AnnotationKind::Comment => {
+                            egui::RichText::new(format!("# {text}")).monospace().weak()
+                        }
+                    };
+                    ui.add(egui::Label::new(text).wrap(false));
+                } else if let Some(symbol) = self
+                    .symbols
+                    .get(&addr)
+                    .filter(|_| kind == AnnotationKind::Label)
+                {
+                    let text = egui::RichText::new(format!("{symbol}:")).monospace().weak();
+                    ui.add(egui::Label::new(text).wrap(false));
+                }
+                if response.double_clicked() {
+                    self.editing = Some(Editing {
+                        addr,
+                        kind,
+                        text: text.unwrap_or_default().to_string(),
+                        focus: true,
+                        error: None,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Save the edited label or comment, invalid labels stay in the editor
+    fn finish_editing(&mut self) {
+        let editing = match &mut self.editing {
+            Some(editing) => editing,
+            None => return,
+        };
+        let annotations = self.annotations.entry(self.image_name.clone()).or_default();
+        match annotations.set(editing.kind, editing.addr, &editing.text) {
+            Ok(()) => {
+                self.editing = None;
+                if let Some(search) = &mut self.search {
+                    search.dirty = true;
+                }
+            }
+            Err(err) => {
+                editing.error = Some(err);
+                editing.focus = true;
+            }
+        }
+    }
+
+    fn show_toolbar(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui
+                .checkbox(&mut self.follow_pc, "Follow PC")
+                .on_hover_text("Scroll to the current instruction when the simulator stops")
+                .changed()
+                && self.follow_pc
+            {
+                self.scroll_to = self.pc;
+            }
+            if let Some(pc) = self.pc {
+                ui.monospace(format!("PC: {pc:016x}"));
+            }
+        });
+        ui.horizontal(|ui| {
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.goto_text)
+                    .hint_text("label, address or pc")
+                    .desired_width(120.0),
+            );
+            let enter = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if ui.button("Go to").clicked() || enter {
+                self.goto();
+            }
+            if ui.button("PC").clicked() {
+                self.scroll_to = self.pc;
+            }
+            let pc_instr = self.pc.and_then(|pc| Some((self.instr_at(pc)?, pc)));
+            if ui
+                .add_enabled(pc_instr.is_some(), egui::Button::new("Decode PC"))
+                .on_hover_text("Open the current instruction in the decoder")
+                .clicked()
+            {
+                self.decode_request = pc_instr;
+            }
+            if ui.button("Regions...").clicked() && self.region_editor.is_none() {
+                self.region_editor = Some(RegionEditor {
+                    from_text: String::new(),
+                    to_text: String::new(),
+                    kind: RegionKind::Words,
+                    error: None,
+                });
+            }
+            if ui.button("Export...").clicked() && self.export.is_none() {
+                self.export = Some(Export {
+                    whole_image: true,
+                    from_text: String::new(),
+                    to_text: String::new(),
+                    pending: None,
+                    status: None,
+                });
+            }
+            if let Some(err) = &self.goto_error {
+                ui.colored_label(ui.visuals().error_fg_color, err);
+            }
+        });
+        ui.horizontal(|ui| {
+            let response = ui
+                .add(
+                    egui::TextEdit::singleline(&mut self.search_text)
+                        .hint_text("search, e.g., sw sp")
+                        .desired_width(120.0),
+                )
+                .on_hover_text(SEARCH_HELP);
+            if response.changed() {
+                self.start_search();
+            }
+            let enter = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if ui.button("⏶").on_hover_text("Previous match").clicked() {
+                self.next_match(false);
+            }
+            if ui.button("⏷").on_hover_text("Next match").clicked() || enter {
+                self.next_match(true);
+            }
+            if enter {
+                response.request_focus();
+            }
+            match (&self.search, SearchQuery::parse(&self.search_text)) {
+                (_, Err(err)) if !self.search_text.trim().is_empty() => {
+                    ui.colored_label(ui.visuals().error_fg_color, err);
+                }
+                (Some(search), _) if search.dirty => {
+                    ui.label("searching...");
+                }
+                (Some(search), _) if search.matches.is_empty() => {
+                    ui.label("no matches");
+                }
+                (Some(search), _) => {
+                    ui.label(format!("{}/{}", search.current + 1, search.matches.len()));
+                }
+                _ => {}
+            }
+        });
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, breakpoints: &mut Breakpoints) {
         let mut open = self.open;
         egui::Window::new("Instructions")
             .open(&mut open)
             .resizable(true)
             .default_width(400.0)
             .show(ctx, |ui| {
-                egui::ScrollArea::vertical().show(ui, |ui| self.show_table(ui));
+                self.update_rows();
+                self.update_search();
+                self.show_toolbar(ui);
+                match self.range {
+                    Some(_) => self.show_table(ui, breakpoints),
+                    None => {
+                        ui.label("No program loaded");
+                    }
+                }
             });
         self.open = open;
+        self.show_export_window(ctx);
+        if self.region_editor.is_some() {
+            self.show_region_editor(ctx);
+        }
     }
 
-    fn show_table(&self, ui: &mut egui::Ui) {
+    fn show_table(&mut self, ui: &mut egui::Ui, breakpoints: &mut Breakpoints) {
         use egui_extras::{Column, TableBuilder};
 
         let text_height = egui::TextStyle::Body.resolve(ui.style()).size;
+        let num_rows = self.num_rows;
+        let pc_color = ui.visuals().selection.bg_fill;
+        let prev_pc_color = pc_color.gamma_multiply(0.4);
+        let match_color = ui.visuals().warn_fg_color.gamma_multiply(0.3);
+        let current_match_color = ui.visuals().warn_fg_color.gamma_multiply(0.6);
+        let arrows = self.visible_arrows();
+        let mut visible_rows: Option<(usize, usize)> = None;
 
-        let table = TableBuilder::new(ui)
+        let mut table = TableBuilder::new(ui)
             .striped(true)
             .resizable(true)
             .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
-            .column(Column::auto())
+            .column(Column::exact(2.0 * text_height + 4.0))
+            .column(Column::exact(crate::branch_arrows::column_width()))
             .column(Column::initial(100.0).at_least(40.0).clip(false))
-            .column(Column::initial(100.0).at_least(40.0).clip(true))
+            .column(Column::initial(80.0).at_least(40.0).clip(true))
+            .column(Column::initial(80.0).at_least(40.0).clip(true))
+            .column(Column::initial(160.0).at_least(40.0).clip(true))
             .column(Column::remainder())
             .min_scrolled_height(1.0);
+        if let Some(row) = self
+            .scroll_to
+            .take()
+            .and_then(|addr| self.addr_to_row(addr))
+        {
+            table = table.scroll_to_row(row, Some(egui::Align::Center));
+        }
 
         table
             .header(40.0, |mut header| {
+                header.col(|ui| {
+                    ui.strong("");
+                });
                 header.col(|ui| {
                     ui.strong("");
                 });
                 header.col(|ui| {
                     ui.strong("Address");
                 });
+                header.col(|ui| {
+                    ui.strong("Label");
+                });
+                header.col(|ui| {
+                    ui.strong("Encoding");
+                });
                 header.col(|ui| {
                     ui.strong("Instructions");
                 });
@@ -62,24 +1252,355 @@ impl InstrList {
                 });
             })
             .body(|body| {
-                body.rows(text_height, 100, |row_index, mut row| {
+                body.rows(text_height, num_rows, |row_index, mut row| {
+                    let addr = self.row_addr(row_index).unwrap_or_default();
+                    visible_rows = Some(match visible_rows {
+                        Some((first, last)) => (first.min(row_index), last.max(row_index)),
+                        None => (row_index, row_index),
+                    });
+                    let content = self.row_content(row_index);
+                    let row_color = if self.pc == Some(addr) {
+                        Some(pc_color)
+                    } else if self.prev_pc == Some(addr) {
+                        Some(prev_pc_color)
+                    } else {
+                        self.search.as_ref().and_then(|search| {
+                            if search.matches.get(search.current) == Some(&addr) {
+                                Some(current_match_color)
+                            } else if search.matches.binary_search(&addr).is_ok() {
+                                Some(match_color)
+                            } else {
+                                None
+                            }
+                        })
+                    };
+                    row.col(|ui| {
+                        cell_background(ui, row_color);
+                        show_gutter(ui, addr, self.pc == Some(addr), breakpoints);
+                    });
                     row.col(|ui| {
-                        ui.label(row_index.to_string());
+                        cell_background(ui, row_color);
+                        draw_arrows(ui, row_index, &arrows);
                     });
                     row.col(|ui| {
-                        ui.label(text_sample(row_index));
+                        cell_background(ui, row_color);
+                        ui.monospace(format!("{addr:016x}")).context_menu(|ui| {
+                            if let Some(RowContent::Instr(instr)) = content {
+                                if ui.button("Open in decoder").clicked() {
+                                    self.decode_request = Some((instr, addr));
+                                    ui.close_menu();
+                                }
+                            }
+                            if ui.button("Mark region from here...").clicked() {
+                                self.open_region_editor(addr);
+                                ui.close_menu();
+                            }
+                        });
                     });
                     row.col(|ui| {
-                        ui.label(text_sample(row_index));
+                        cell_background(ui, row_color);
+                        self.annotation_cell(ui, addr, AnnotationKind::Label);
                     });
                     row.col(|ui| {
-                        ui.add(egui::Label::new("Thousands of rows of even height").wrap(false));
+                        cell_background(ui, row_color);
+                        match &content {
+                            Some(RowContent::Instr(instr)) if is_compressed(*instr) => {
+                                ui.monospace(format!("{instr:04x}"))
+                                    .on_hover_text(format!("expands to {:08x}", expand(*instr)));
+                            }
+                            Some(RowContent::Instr(instr)) => {
+                                ui.monospace(format!("{instr:08x}"));
+                            }
+                            Some(RowContent::Data(_, bytes)) => {
+                                ui.monospace(data_hex(bytes));
+                            }
+                            None => {}
+                        }
+                    });
+                    row.col(|ui| {
+                        cell_background(ui, row_color);
+                        match content {
+                            Some(RowContent::Instr(instr)) => {
+                                let regs = self.regs.as_ref().filter(|_| self.pc == Some(addr));
+                                ui.add(
+                                    egui::Label::new(
+                                        egui::RichText::new(disasm(expand(instr), addr))
+                                            .monospace(),
+                                    )
+                                    .wrap(false),
+                                )
+                                .on_hover_ui(|ui| instr_tooltip(ui, instr, addr, regs));
+                                if let Some(c) =
+                                    decode_compressed(instr as u16).filter(|_| is_compressed(instr))
+                                {
+                                    ui.add(
+                                        egui::Label::new(
+                                            egui::RichText::new(c.name).monospace().weak(),
+                                        )
+                                        .wrap(false),
+                                    );
+                                }
+                            }
+                            Some(RowContent::Data(kind, bytes)) => {
+                                ui.add(
+                                    egui::Label::new(
+                                        egui::RichText::new(data_directive(kind, &bytes))
+                                            .monospace(),
+                                    )
+                                    .wrap(false),
+                                );
+                            }
+                            None => {
+                                ui.label("...");
+                            }
+                        }
+                    });
+                    row.col(|ui| {
+                        cell_background(ui, row_color);
+                        self.annotation_cell(ui, addr, AnnotationKind::Comment);
+                        match self.last_branch {
+                            Some((branch_addr, true)) if branch_addr == addr => {
+                                ui.label("taken");
+                            }
+                            Some((branch_addr, false)) if branch_addr == addr => {
+                                ui.label("not taken");
+                            }
+                            _ => {}
+                        }
                     });
                 })
             });
+        // arrows are laid out for the rows of the previous frame, redraw after scrolling
+        if visible_rows != self.visible_rows {
+            self.visible_rows = visible_rows;
+            self.request_visible_windows();
+            ui.ctx().request_repaint();
+        }
     }
 }
 
-fn text_sample(row_index: usize) -> String {
-    format!("txt {row_index}")
+fn reg_name(reg: usize) -> String {
+    format!("{} (x{reg})", ABI_NAMES[reg])
+}
+
+/// Fields and semantics of the instruction. `regs` - register values
+/// when the simulator is stopped at the instruction.
+fn instr_tooltip(ui: &mut egui::Ui, instr: u32, addr: u64, regs: Option<&[u64; 32]>) {
+    let instr32 = expand(instr);
+    let def = match lookup(instr32) {
+        Some(def) => def,
+        None => {
+            ui.label(format!("Unknown instruction: {}", diagnose(instr32)));
+            return;
+        }
+    };
+    let mut title = format!("{} - {:?}-type, {}", def.name, def.format, def.ext.name());
+    if let Some(c) = decode_compressed(instr as u16).filter(|_| is_compressed(instr)) {
+        title = format!("{} expands to {title}", c.name);
+    }
+    ui.strong(title);
+    let fields = decode_fields(instr32);
+    egui::Grid::new("instr_tooltip").show(ui, |ui| {
+        for (name, reg) in [("rd", fields.rd), ("rs1", fields.rs1), ("rs2", fields.rs2)] {
+            if let Some(reg) = reg {
+                ui.monospace(name);
+                ui.monospace(reg_name(reg));
+                ui.end_row();
+            }
+        }
+        if let Some(imm) = fields.imm {
+            ui.monospace("imm");
+            ui.monospace(format!("{imm} (0x{imm:x})"));
+            ui.end_row();
+        }
+    });
+    ui.monospace(def.pseudo);
+
+    let x = match regs {
+        Some(x) => x,
+        None => return,
+    };
+    ui.separator();
+    egui::Grid::new("instr_tooltip_values").show(ui, |ui| {
+        for reg in [fields.rs1, fields.rs2].into_iter().flatten() {
+            ui.monospace(reg_name(reg));
+            ui.monospace(format!("0x{:016x} ({})", x[reg], x[reg] as i64));
+            ui.end_row();
+        }
+        let preview = preview(def, instr32, instr_len(instr), addr, x);
+        if let (Some(result), Some(rd)) = (preview.result, fields.rd) {
+            ui.monospace(format!("{} ←", ABI_NAMES[rd]));
+            ui.monospace(format!("0x{result:016x} ({})", result as i64));
+            ui.end_row();
+        }
+        if let Some(mem_addr) = preview.mem_addr {
+            ui.monospace("address");
+            ui.monospace(format!("0x{mem_addr:016x}"));
+            ui.end_row();
+        }
+        if let Some(next_pc) = preview.next_pc {
+            ui.monospace("next pc");
+            ui.monospace(format!("0x{next_pc:016x}"));
+            ui.end_row();
+        }
+    });
+}
+
+/// Encoding column of a data row, long rows are shortened
+fn data_hex(bytes: &[u8]) -> String {
+    const MAX_BYTES: usize = 8;
+    let hex: String = bytes
+        .iter()
+        .take(MAX_BYTES)
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    if bytes.len() > MAX_BYTES {
+        hex + "…"
+    } else {
+        hex
+    }
+}
+
+/// Breakpoint marker and PC arrow. Click toggles a breakpoint,
+/// right-click opens breakpoint options.
+fn show_gutter(ui: &mut egui::Ui, addr: u64, is_pc: bool, breakpoints: &mut Breakpoints) {
+    let rect = ui.max_rect();
+    let response = ui.interact(rect, ui.id().with(addr), egui::Sense::click());
+    let marker_size = rect.height().min(rect.width() * 0.5);
+    if let Some(bp) = breakpoints.get(addr) {
+        let center = egui::pos2(rect.left() + marker_size * 0.5, rect.center().y);
+        let radius = marker_size * 0.35;
+        let color = egui::Color32::from_rgb(220, 40, 40);
+        if bp.enabled {
+            ui.painter().circle_filled(center, radius, color);
+        } else {
+            ui.painter()
+                .circle_stroke(center, radius, egui::Stroke::new(1.5, color));
+        }
+    }
+    if is_pc {
+        ui.add_space(marker_size);
+        ui.label("▶");
+    }
+    if response.clicked() {
+        breakpoints.toggle(addr);
+    }
+    response.context_menu(|ui| breakpoint_menu(ui, addr, breakpoints));
+}
+
+fn breakpoint_menu(ui: &mut egui::Ui, addr: u64, breakpoints: &mut Breakpoints) {
+    let (enabled, mut condition) = match breakpoints.get(addr) {
+        Some(bp) => (bp.enabled, bp.condition.clone()),
+        None => {
+            if ui.button("Add breakpoint").clicked() {
+                breakpoints.add(addr);
+                ui.close_menu();
+            }
+            return;
+        }
+    };
+    if ui
+        .button(if enabled { "Disable" } else { "Enable" })
+        .clicked()
+    {
+        breakpoints.set_enabled(addr, !enabled);
+        ui.close_menu();
+    }
+    if ui.button("Remove").clicked() {
+        breakpoints.remove(addr);
+        ui.close_menu();
+    }
+    ui.separator();
+    ui.horizontal(|ui| {
+        ui.label("Condition");
+        if ui
+            .add(egui::TextEdit::singleline(&mut condition).hint_text("e.g., a0 == 5"))
+            .changed()
+        {
+            breakpoints.set_condition(addr, condition.clone());
+        }
+    });
+    if let Err(err) = Condition::parse(&condition) {
+        ui.colored_label(ui.visuals().error_fg_color, err);
+    }
+}
+
+/// Highlight the whole table cell
+fn cell_background(ui: &mut egui::Ui, color: Option<egui::Color32>) {
+    if let Some(color) = color {
+        let rect = ui.max_rect().expand2(0.5 * ui.spacing().item_spacing);
+        ui.painter().rect_filled(rect, 0.0, color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: u64 = 0x8000_0000;
+
+    /// c.nop everywhere except a 32-bit nop at 1022 crossing the first window boundary
+    fn image() -> Vec<u8> {
+        (0..3000u64)
+            .map(|i| match i {
+                1022 => 0x13,
+                1023..=1025 => 0x00,
+                _ if i % 2 == 0 => 0x01,
+                _ => 0x00,
+            })
+            .collect()
+    }
+
+    fn answer_requests(list: &mut InstrList, image: &[u8]) {
+        for (addr, len) in list.take_mem_requests() {
+            let offset = (addr - BASE) as usize;
+            list.memory_received(addr, image[offset..offset + len as usize].to_vec());
+        }
+    }
+
+    #[test]
+    fn rows_of_fetched_windows() {
+        let image = image();
+        let mut list = InstrList::default();
+        list.set_image("test", BASE, image.len() as u64);
+        list.update_rows();
+        // nothing is fetched until it is shown, 4-byte rows meanwhile
+        assert_eq!(list.num_rows, 750);
+        assert!(list.take_mem_requests().is_empty());
+
+        list.row_content(300);
+        assert_eq!(list.pending_requests, vec![(BASE + 1024, 1024)]);
+        answer_requests(&mut list, &image);
+        list.update_rows();
+        assert_eq!(list.num_rows, 256 + 512 + 238);
+
+        // the 32-bit instruction at the end of the first window moves the rows of the second one
+        list.row_content(0);
+        answer_requests(&mut list, &image);
+        list.update_rows();
+        assert_eq!(list.num_rows, 512 + 511 + 238);
+        assert_eq!(list.row_addr(511), Some(BASE + 1022));
+        assert_eq!(list.row_addr(512), Some(BASE + 1026));
+        assert_eq!(list.addr_to_row(BASE + 1024), Some(511));
+        assert_eq!(list.instr_at(BASE + 1022), Some(0x13));
+        assert_eq!(list.row_addr(1023), Some(BASE + 2048));
+        assert_eq!(list.addr_to_row(BASE + 2999), Some(list.num_rows - 1));
+    }
+
+    #[test]
+    fn refresh_fetches_visible_windows() {
+        let image = image();
+        let mut list = InstrList::default();
+        list.set_image("test", BASE, image.len() as u64);
+        list.update_rows();
+        list.all_rows();
+        answer_requests(&mut list, &image);
+        list.update_rows();
+        list.visible_rows = Some((0, 10));
+        list.refresh();
+        assert_eq!(
+            list.take_mem_requests(),
+            vec![(BASE, 1024), (BASE + 1024, 1024)]
+        );
+    }
 }
@@ -0,0 +1,63 @@
+use crate::{instr_format::decode_fields, number::parse_int, regs::parse_reg};
+
+/// Help text for the search field
+pub const SEARCH_HELP: &str = "Space separated terms, all of them must match:\n\
+    sw - mnemonic\n\
+    sp - register operand\n\
+    w:a0 - writes to register\n\
+    c:loop - label or comment contains the text\n\
+    -8, 0x10 - immediate";
+
+enum Term {
+    Mnemonic(String),
+    UsesReg(usize),
+    WritesReg(usize),
+    Imm(i64),
+    /// Lowercase text to find in the label or comment
+    Note(String),
+}
+
+/// Search query over disassembled instructions, e.g., "sw sp" - all stores using sp
+pub struct SearchQuery {
+    terms: Vec<Term>,
+}
+
+impl SearchQuery {
+    pub fn parse(text: &str) -> Result<SearchQuery, String> {
+        let mut terms = Vec::new();
+        for term in text.split_whitespace() {
+            let term = if let Some(text) = term.strip_prefix("c:") {
+                Term::Note(text.to_lowercase())
+            } else if let Some(reg) = term.strip_prefix("w:") {
+                Term::WritesReg(parse_reg(reg).ok_or_else(|| format!("unknown register: {reg}"))?)
+            } else if let Some(reg) = parse_reg(term) {
+                Term::UsesReg(reg)
+            } else if let Some(imm) = parse_int(term) {
+                Term::Imm(imm)
+            } else {
+                Term::Mnemonic(term.to_lowercase())
+            };
+            terms.push(term);
+        }
+        if terms.is_empty() {
+            return Err("empty search".to_string());
+        }
+        Ok(SearchQuery { terms })
+    }
+
+    /// `notes` - user label and comment of the instruction
+    pub fn matches(&self, instr: u32, disasm: &str, notes: &str) -> bool {
+        let fields = decode_fields(instr);
+        let mnemonic = disasm.split_whitespace().next().unwrap_or_default();
+        self.terms.iter().all(|term| match term {
+            Term::Mnemonic(m) => mnemonic.eq_ignore_ascii_case(m),
+            Term::UsesReg(reg) => {
+                fields.rd == Some(*reg) || fields.rs1 == Some(*reg) || fields.rs2 == Some(*reg)
+            }
+            // x0 is hardwired to zero
+            Term::WritesReg(reg) => *reg != 0 && fields.rd == Some(*reg),
+            Term::Imm(imm) => fields.imm == Some(*imm),
+            Term::Note(text) => notes.to_lowercase().contains(text.as_str()),
+        })
+    }
+}
@@ -0,0 +1,476 @@
+//! Table of the known 32-bit instructions: encodings, ISA extensions and semantics
+
+use crate::instr_format::{bits, decode_fields, opcode, InstrFormat};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Extension {
+    I,
+    M,
+    A,
+    Zicsr,
+    Zifencei,
+    Privileged,
+}
+
+impl Extension {
+    pub const ALL: [Extension; 6] = [
+        Extension::I,
+        Extension::M,
+        Extension::A,
+        Extension::Zicsr,
+        Extension::Zifencei,
+        Extension::Privileged,
+    ];
+
+    /// The simulator implements RV64I as advertised in the device tree
+    /// and CSR access, e.g., the demos read mhartid
+    pub fn simulated(self) -> bool {
+        matches!(self, Extension::I | Extension::Zicsr)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Extension::I => "RV64I",
+            Extension::M => "M (multiply/divide)",
+            Extension::A => "A (atomics)",
+            Extension::Zicsr => "Zicsr (CSR access)",
+            Extension::Zifencei => "Zifencei (instruction fence)",
+            Extension::Privileged => "Privileged",
+        }
+    }
+}
+
+pub struct InstrDef {
+    pub name: &'static str,
+    pub format: InstrFormat,
+    /// Bits of the encoding fixed for the instruction and their values
+    pub mask: u32,
+    pub value: u32,
+    pub ext: Extension,
+    /// One-line semantics in the notation of the ISA manual
+    pub pseudo: &'static str,
+}
+
+const fn def(
+    name: &'static str,
+    format: InstrFormat,
+    (mask, value): (u32, u32),
+    ext: Extension,
+    pseudo: &'static str,
+) -> InstrDef {
+    InstrDef {
+        name,
+        format,
+        mask,
+        value,
+        ext,
+        pseudo,
+    }
+}
+
+/// opcode
+const fn op(opcode: u32) -> (u32, u32) {
+    (0x7f, opcode)
+}
+
+/// opcode and funct3
+const fn f3(opcode: u32, funct3: u32) -> (u32, u32) {
+    (0x707f, funct3 << 12 | opcode)
+}
+
+/// opcode, funct3 and funct7
+const fn f7(opcode: u32, funct3: u32, funct7: u32) -> (u32, u32) {
+    (0xfe00_707f, funct7 << 25 | funct3 << 12 | opcode)
+}
+
+/// RV64 shifts by an immediate have 6-bit shamt and funct6
+const fn f6(opcode: u32, funct3: u32, funct6: u32) -> (u32, u32) {
+    (0xfc00_707f, funct6 << 26 | funct3 << 12 | opcode)
+}
+
+/// Atomics: funct5 and width, aq/rl bits are not fixed
+const fn amo(funct5: u32, funct3: u32) -> (u32, u32) {
+    (0xf800_707f, funct5 << 27 | funct3 << 12 | opcode::AMO)
+}
+
+/// Load-reserved has rs2 = 0
+const fn lr(funct3: u32) -> (u32, u32) {
+    (0xf9f0_707f, 0b00010 << 27 | funct3 << 12 | opcode::AMO)
+}
+
+const fn exact(instr: u32) -> (u32, u32) {
+    (0xffff_ffff, instr)
+}
+
+use Extension::{Privileged, Zicsr, Zifencei, A, I, M};
+use InstrFormat::{B as FB, I as FI, J as FJ, R as FR, S as FS, U as FU};
+
+#[rustfmt::skip]
+pub const INSTRS: &[InstrDef] = &[
+    // RV64I
+    def("lui", FU, op(opcode::LUI), I, "x[rd] = sext(imm[31:12] << 12)"),
+    def("auipc", FU, op(opcode::AUIPC), I, "x[rd] = pc + sext(imm[31:12] << 12)"),
+    def("jal", FJ, op(opcode::JAL), I, "x[rd] = pc + 4; pc += sext(offset)"),
+    def("jalr", FI, f3(opcode::JALR, 0), I, "t = pc + 4; pc = (x[rs1] + sext(offset)) & ~1; x[rd] = t"),
+    def("beq", FB, f3(opcode::BRANCH, 0), I, "if (x[rs1] == x[rs2]) pc += sext(offset)"),
+    def("bne", FB, f3(opcode::BRANCH, 1), I, "if (x[rs1] != x[rs2]) pc += sext(offset)"),
+    def("blt", FB, f3(opcode::BRANCH, 4), I, "if (x[rs1] <s x[rs2]) pc += sext(offset)"),
+    def("bge", FB, f3(opcode::BRANCH, 5), I, "if (x[rs1] >=s x[rs2]) pc += sext(offset)"),
+    def("bltu", FB, f3(opcode::BRANCH, 6), I, "if (x[rs1] <u x[rs2]) pc += sext(offset)"),
+    def("bgeu", FB, f3(opcode::BRANCH, 7), I, "if (x[rs1] >=u x[rs2]) pc += sext(offset)"),
+    def("lb", FI, f3(opcode::LOAD, 0), I, "x[rd] = sext(M[x[rs1] + sext(offset)][7:0])"),
+    def("lh", FI, f3(opcode::LOAD, 1), I, "x[rd] = sext(M[x[rs1] + sext(offset)][15:0])"),
+    def("lw", FI, f3(opcode::LOAD, 2), I, "x[rd] = sext(M[x[rs1] + sext(offset)][31:0])"),
+    def("ld", FI, f3(opcode::LOAD, 3), I, "x[rd] = M[x[rs1] + sext(offset)][63:0]"),
+    def("lbu", FI, f3(opcode::LOAD, 4), I, "x[rd] = zext(M[x[rs1] + sext(offset)][7:0])"),
+    def("lhu", FI, f3(opcode::LOAD, 5), I, "x[rd] = zext(M[x[rs1] + sext(offset)][15:0])"),
+    def("lwu", FI, f3(opcode::LOAD, 6), I, "x[rd] = zext(M[x[rs1] + sext(offset)][31:0])"),
+    def("sb", FS, f3(opcode::STORE, 0), I, "M[x[rs1] + sext(offset)] = x[rs2][7:0]"),
+    def("sh", FS, f3(opcode::STORE, 1), I, "M[x[rs1] + sext(offset)] = x[rs2][15:0]"),
+    def("sw", FS, f3(opcode::STORE, 2), I, "M[x[rs1] + sext(offset)] = x[rs2][31:0]"),
+    def("sd", FS, f3(opcode::STORE, 3), I, "M[x[rs1] + sext(offset)] = x[rs2][63:0]"),
+    def("addi", FI, f3(opcode::OP_IMM, 0), I, "x[rd] = x[rs1] + sext(imm)"),
+    def("slti", FI, f3(opcode::OP_IMM, 2), I, "x[rd] = x[rs1] <s sext(imm)"),
+    def("sltiu", FI, f3(opcode::OP_IMM, 3), I, "x[rd] = x[rs1] <u sext(imm)"),
+    def("xori", FI, f3(opcode::OP_IMM, 4), I, "x[rd] = x[rs1] ^ sext(imm)"),
+    def("ori", FI, f3(opcode::OP_IMM, 6), I, "x[rd] = x[rs1] | sext(imm)"),
+    def("andi", FI, f3(opcode::OP_IMM, 7), I, "x[rd] = x[rs1] & sext(imm)"),
+    def("slli", FI, f6(opcode::OP_IMM, 1, 0), I, "x[rd] = x[rs1] << shamt"),
+    def("srli", FI, f6(opcode::OP_IMM, 5, 0), I, "x[rd] = x[rs1] >>u shamt"),
+    def("srai", FI, f6(opcode::OP_IMM, 5, 0b01_0000), I, "x[rd] = x[rs1] >>s shamt"),
+    def("add", FR, f7(opcode::OP, 0, 0), I, "x[rd] = x[rs1] + x[rs2]"),
+    def("sub", FR, f7(opcode::OP, 0, 0b010_0000), I, "x[rd] = x[rs1] - x[rs2]"),
+    def("sll", FR, f7(opcode::OP, 1, 0), I, "x[rd] = x[rs1] << x[rs2][5:0]"),
+    def("slt", FR, f7(opcode::OP, 2, 0), I, "x[rd] = x[rs1] <s x[rs2]"),
+    def("sltu", FR, f7(opcode::OP, 3, 0), I, "x[rd] = x[rs1] <u x[rs2]"),
+    def("xor", FR, f7(opcode::OP, 4, 0), I, "x[rd] = x[rs1] ^ x[rs2]"),
+    def("srl", FR, f7(opcode::OP, 5, 0), I, "x[rd] = x[rs1] >>u x[rs2][5:0]"),
+    def("sra", FR, f7(opcode::OP, 5, 0b010_0000), I, "x[rd] = x[rs1] >>s x[rs2][5:0]"),
+    def("or", FR, f7(opcode::OP, 6, 0), I, "x[rd] = x[rs1] | x[rs2]"),
+    def("and", FR, f7(opcode::OP, 7, 0), I, "x[rd] = x[rs1] & x[rs2]"),
+    def("addiw", FI, f3(opcode::OP_IMM_32, 0), I, "x[rd] = sext((x[rs1] + sext(imm))[31:0])"),
+    def("slliw", FI, f7(opcode::OP_IMM_32, 1, 0), I, "x[rd] = sext((x[rs1] << shamt)[31:0])"),
+    def("srliw", FI, f7(opcode::OP_IMM_32, 5, 0), I, "x[rd] = sext(x[rs1][31:0] >>u shamt)"),
+    def("sraiw", FI, f7(opcode::OP_IMM_32, 5, 0b010_0000), I, "x[rd] = sext(x[rs1][31:0] >>s shamt)"),
+    def("addw", FR, f7(opcode::OP_32, 0, 0), I, "x[rd] = sext((x[rs1] + x[rs2])[31:0])"),
+    def("subw", FR, f7(opcode::OP_32, 0, 0b010_0000), I, "x[rd] = sext((x[rs1] - x[rs2])[31:0])"),
+    def("sllw", FR, f7(opcode::OP_32, 1, 0), I, "x[rd] = sext((x[rs1] << x[rs2][4:0])[31:0])"),
+    def("srlw", FR, f7(opcode::OP_32, 5, 0), I, "x[rd] = sext(x[rs1][31:0] >>u x[rs2][4:0])"),
+    def("sraw", FR, f7(opcode::OP_32, 5, 0b010_0000), I, "x[rd] = sext(x[rs1][31:0] >>s x[rs2][4:0])"),
+    def("fence", FI, f3(opcode::MISC_MEM, 0), I, "Fence(pred, succ)"),
+    def("ecall", FI, exact(0x0000_0073), I, "RaiseException(EnvironmentCall)"),
+    def("ebreak", FI, exact(0x0010_0073), I, "RaiseException(Breakpoint)"),
+    // Zifencei
+    def("fence.i", FI, f3(opcode::MISC_MEM, 1), Zifencei, "Fence(Store, Fetch)"),
+    // Zicsr
+    def("csrrw", FI, f3(opcode::SYSTEM, 1), Zicsr, "t = CSRs[csr]; CSRs[csr] = x[rs1]; x[rd] = t"),
+    def("csrrs", FI, f3(opcode::SYSTEM, 2), Zicsr, "t = CSRs[csr]; CSRs[csr] = t | x[rs1]; x[rd] = t"),
+    def("csrrc", FI, f3(opcode::SYSTEM, 3), Zicsr, "t = CSRs[csr]; CSRs[csr] = t & ~x[rs1]; x[rd] = t"),
+    def("csrrwi", FI, f3(opcode::SYSTEM, 5), Zicsr, "x[rd] = CSRs[csr]; CSRs[csr] = zimm"),
+    def("csrrsi", FI, f3(opcode::SYSTEM, 6), Zicsr, "t = CSRs[csr]; CSRs[csr] = t | zimm; x[rd] = t"),
+    def("csrrci", FI, f3(opcode::SYSTEM, 7), Zicsr, "t = CSRs[csr]; CSRs[csr] = t & ~zimm; x[rd] = t"),
+    // M
+    def("mul", FR, f7(opcode::OP, 0, 1), M, "x[rd] = x[rs1] * x[rs2]"),
+    def("mulh", FR, f7(opcode::OP, 1, 1), M, "x[rd] = (x[rs1] s*s x[rs2]) >>s 64"),
+    def("mulhsu", FR, f7(opcode::OP, 2, 1), M, "x[rd] = (x[rs1] s*u x[rs2]) >>s 64"),
+    def("mulhu", FR, f7(opcode::OP, 3, 1), M, "x[rd] = (x[rs1] u*u x[rs2]) >>u 64"),
+    def("div", FR, f7(opcode::OP, 4, 1), M, "x[rd] = x[rs1] /s x[rs2]"),
+    def("divu", FR, f7(opcode::OP, 5, 1), M, "x[rd] = x[rs1] /u x[rs2]"),
+    def("rem", FR, f7(opcode::OP, 6, 1), M, "x[rd] = x[rs1] %s x[rs2]"),
+    def("remu", FR, f7(opcode::OP, 7, 1), M, "x[rd] = x[rs1] %u x[rs2]"),
+    def("mulw", FR, f7(opcode::OP_32, 0, 1), M, "x[rd] = sext((x[rs1] * x[rs2])[31:0])"),
+    def("divw", FR, f7(opcode::OP_32, 4, 1), M, "x[rd] = sext(x[rs1][31:0] /s x[rs2][31:0])"),
+    def("divuw", FR, f7(opcode::OP_32, 5, 1), M, "x[rd] = sext(x[rs1][31:0] /u x[rs2][31:0])"),
+    def("remw", FR, f7(opcode::OP_32, 6, 1), M, "x[rd] = sext(x[rs1][31:0] %s x[rs2][31:0])"),
+    def("remuw", FR, f7(opcode::OP_32, 7, 1), M, "x[rd] = sext(x[rs1][31:0] %u x[rs2][31:0])"),
+    // A
+    def("lr.w", FR, lr(2), A, "x[rd] = LoadReserved32(M[x[rs1]])"),
+    def("sc.w", FR, amo(0b00011, 2), A, "x[rd] = StoreConditional32(M[x[rs1]], x[rs2])"),
+    def("amoswap.w", FR, amo(0b00001, 2), A, "x[rd] = AMO32(M[x[rs1]] SWAP x[rs2])"),
+    def("amoadd.w", FR, amo(0b00000, 2), A, "x[rd] = AMO32(M[x[rs1]] + x[rs2])"),
+    def("amoxor.w", FR, amo(0b00100, 2), A, "x[rd] = AMO32(M[x[rs1]] ^ x[rs2])"),
+    def("amoand.w", FR, amo(0b01100, 2), A, "x[rd] = AMO32(M[x[rs1]] & x[rs2])"),
+    def("amoor.w", FR, amo(0b01000, 2), A, "x[rd] = AMO32(M[x[rs1]] | x[rs2])"),
+    def("amomin.w", FR, amo(0b10000, 2), A, "x[rd] = AMO32(M[x[rs1]] MIN x[rs2])"),
+    def("amomax.w", FR, amo(0b10100, 2), A, "x[rd] = AMO32(M[x[rs1]] MAX x[rs2])"),
+    def("amominu.w", FR, amo(0b11000, 2), A, "x[rd] = AMO32(M[x[rs1]] MINU x[rs2])"),
+    def("amomaxu.w", FR, amo(0b11100, 2), A, "x[rd] = AMO32(M[x[rs1]] MAXU x[rs2])"),
+    def("lr.d", FR, lr(3), A, "x[rd] = LoadReserved64(M[x[rs1]])"),
+    def("sc.d", FR, amo(0b00011, 3), A, "x[rd] = StoreConditional64(M[x[rs1]], x[rs2])"),
+    def("amoswap.d", FR, amo(0b00001, 3), A, "x[rd] = AMO64(M[x[rs1]] SWAP x[rs2])"),
+    def("amoadd.d", FR, amo(0b00000, 3), A, "x[rd] = AMO64(M[x[rs1]] + x[rs2])"),
+    def("amoxor.d", FR, amo(0b00100, 3), A, "x[rd] = AMO64(M[x[rs1]] ^ x[rs2])"),
+    def("amoand.d", FR, amo(0b01100, 3), A, "x[rd] = AMO64(M[x[rs1]] & x[rs2])"),
+    def("amoor.d", FR, amo(0b01000, 3), A, "x[rd] = AMO64(M[x[rs1]] | x[rs2])"),
+    def("amomin.d", FR, amo(0b10000, 3), A, "x[rd] = AMO64(M[x[rs1]] MIN x[rs2])"),
+    def("amomax.d", FR, amo(0b10100, 3), A, "x[rd] = AMO64(M[x[rs1]] MAX x[rs2])"),
+    def("amominu.d", FR, amo(0b11000, 3), A, "x[rd] = AMO64(M[x[rs1]] MINU x[rs2])"),
+    def("amomaxu.d", FR, amo(0b11100, 3), A, "x[rd] = AMO64(M[x[rs1]] MAXU x[rs2])"),
+    // Privileged
+    def("mret", FR, exact(0x3020_0073), Privileged, "pc = CSRs[mepc]; mode = mstatus.MPP"),
+    def("sret", FR, exact(0x1020_0073), Privileged, "pc = CSRs[sepc]; mode = sstatus.SPP"),
+    def("wfi", FR, exact(0x1050_0073), Privileged, "WaitForInterrupt()"),
+    def("sfence.vma", FR, (0xfe00_7fff, 0x1200_0073), Privileged, "Fence(Store, AddressTranslation)"),
+];
+
+/// Definition of a 32-bit instruction, None for unknown encodings
+pub fn lookup(instr: u32) -> Option<&'static InstrDef> {
+    INSTRS.iter().find(|def| instr & def.mask == def.value)
+}
+
+/// Extensions the decoder only explains: (name, description)
+pub const OTHER_EXTENSIONS: &[(&str, &str)] = &[
+    (
+        "C",
+        "16-bit compressed instructions, decoded by expanding them",
+    ),
+    ("F", "single-precision floating point, recognized by opcode"),
+    ("D", "double-precision floating point, recognized by opcode"),
+    ("Q", "quad-precision floating point, recognized by opcode"),
+    ("Zfh", "half-precision floating point, recognized by opcode"),
+    ("V", "vector, recognized by opcode"),
+    (
+        "B",
+        "bit manipulation (Zba, Zbb, Zbs), recognized by funct fields",
+    ),
+];
+
+/// Why a 32-bit instruction is not in the table
+pub fn diagnose(instr: u32) -> String {
+    let op = instr & 0x7f;
+    let funct3 = bits(instr, 14, 12);
+    let funct7 = bits(instr, 31, 25);
+    let float = |fmt| match fmt {
+        0 => "F (single-precision)",
+        1 => "D (double-precision)",
+        2 => "Zfh (half-precision)",
+        _ => "Q (quad-precision)",
+    };
+    match op {
+        _ if instr == 0 || instr == 0xffff_ffff => {
+            "all-zeros and all-ones encodings are defined as illegal".to_string()
+        }
+        _ if instr & 0b11 != 0b11 => "16-bit compressed encoding (C extension)".to_string(),
+        _ if instr & 0b1_1100 == 0b1_1100 => {
+            "encoding longer than 32 bits (bits [4:2] are 111)".to_string()
+        }
+        opcode::LOAD_FP | opcode::STORE_FP => match funct3 {
+            1 => "floating-point load/store of the Zfh (half-precision) extension".to_string(),
+            2..=4 => format!(
+                "floating-point load/store of the {} extension",
+                float([0, 1, 3][funct3 as usize - 2])
+            ),
+            _ => "vector load/store of the V extension".to_string(),
+        },
+        opcode::MADD | opcode::MSUB | opcode::NMSUB | opcode::NMADD => format!(
+            "fused multiply-add of the {} extension",
+            float(bits(instr, 26, 25))
+        ),
+        opcode::OP_FP => format!(
+            "floating-point operation of the {} extension",
+            float(bits(instr, 26, 25))
+        ),
+        opcode::OP_V => "vector instruction of the V extension".to_string(),
+        opcode::CUSTOM_0 | opcode::CUSTOM_1 | opcode::CUSTOM_2 | opcode::CUSTOM_3 => {
+            "opcode reserved for custom (vendor) extensions".to_string()
+        }
+        opcode::OP | opcode::OP_32
+            if [0x04, 0x05, 0x10, 0x14, 0x20, 0x24, 0x30, 0x34].contains(&funct7) =>
+        {
+            "bit manipulation instruction of the B extension".to_string()
+        }
+        // shifts by an immediate with other funct bits
+        opcode::OP_IMM | opcode::OP_IMM_32 if funct3 == 1 || funct3 == 5 => {
+            "bit manipulation instruction of the B extension".to_string()
+        }
+        _ => match opcode::name(op) {
+            Some(name) => format!("reserved funct field values of the {name} opcode"),
+            None => format!("unknown opcode 0b{op:07b}"),
+        },
+    }
+}
+
+/// Exceptions the instruction can raise
+pub fn exceptions(def: &InstrDef) -> &'static [&'static str] {
+    match (def.value & 0x7f, def.name) {
+        (opcode::LOAD, _) | (opcode::AMO, "lr.w" | "lr.d") => &[
+            "Load address misaligned",
+            "Load access fault",
+            "Load page fault",
+        ],
+        (opcode::STORE | opcode::AMO, _) => &[
+            "Store/AMO address misaligned",
+            "Store/AMO access fault",
+            "Store/AMO page fault",
+        ],
+        (opcode::JAL | opcode::JALR | opcode::BRANCH, _) => &[
+            "Instruction address misaligned (target not 4-byte aligned without the C extension)",
+        ],
+        (_, "ecall") => &["Environment call from U-, S- or M-mode"],
+        (_, "ebreak") => &["Breakpoint"],
+        (_, "mret" | "sret" | "wfi" | "sfence.vma") => &[
+            "Illegal instruction in a less privileged mode or when trapped by mstatus.TSR/TW/TVM",
+        ],
+        (opcode::SYSTEM, _) => &[
+            "Illegal instruction if the CSR doesn't exist, is read-only and written \
+             or needs a higher privilege",
+        ],
+        _ => &[],
+    }
+}
+
+/// Effects of an instruction computed from the register values before it executes
+#[derive(Default)]
+pub struct Preview {
+    /// Value written to rd
+    pub result: Option<u64>,
+    /// Effective address of a memory access
+    pub mem_addr: Option<u64>,
+    /// Next PC of a branch or jump
+    pub next_pc: Option<u64>,
+}
+
+fn sext32(value: u64) -> u64 {
+    value as i32 as i64 as u64
+}
+
+/// Is the conditional branch taken with the values of rs1 and rs2
+pub fn branch_taken(def: &InstrDef, a: u64, b: u64) -> bool {
+    match def.name {
+        "beq" => a == b,
+        "bne" => a != b,
+        "blt" => (a as i64) < (b as i64),
+        "bge" => (a as i64) >= (b as i64),
+        "bltu" => a < b,
+        _ => a >= b,
+    }
+}
+
+/// `instr` - the expanded form of compressed instructions,
+/// `len` - length of the original instruction in bytes
+pub fn preview(def: &InstrDef, instr: u32, len: u64, pc: u64, x: &[u64; 32]) -> Preview {
+    let fields = decode_fields(instr);
+    let a = fields.rs1.map_or(0, |rs1| x[rs1]);
+    let b = fields.rs2.map_or(0, |rs2| x[rs2]);
+    let imm = fields.imm.unwrap_or(0) as u64;
+    let shamt = (imm & 0x3f) as u32;
+    let (a32, b32) = (a as u32, b as u32);
+    let result = match def.name {
+        "lui" => Some(imm),
+        "auipc" => Some(pc.wrapping_add(imm)),
+        "jal" | "jalr" => Some(pc.wrapping_add(len)),
+        "addi" => Some(a.wrapping_add(imm)),
+        "slti" => Some(((a as i64) < (imm as i64)) as u64),
+        "sltiu" => Some((a < imm) as u64),
+        "xori" => Some(a ^ imm),
+        "ori" => Some(a | imm),
+        "andi" => Some(a & imm),
+        "slli" => Some(a << shamt),
+        "srli" => Some(a >> shamt),
+        "srai" => Some(((a as i64) >> shamt) as u64),
+        "add" => Some(a.wrapping_add(b)),
+        "sub" => Some(a.wrapping_sub(b)),
+        "sll" => Some(a << (b & 0x3f)),
+        "slt" => Some(((a as i64) < (b as i64)) as u64),
+        "sltu" => Some((a < b) as u64),
+        "xor" => Some(a ^ b),
+        "srl" => Some(a >> (b & 0x3f)),
+        "sra" => Some(((a as i64) >> (b & 0x3f)) as u64),
+        "or" => Some(a | b),
+        "and" => Some(a & b),
+        "addiw" => Some(sext32(a.wrapping_add(imm))),
+        "slliw" => Some(sext32((a32 << (shamt & 0x1f)) as u64)),
+        "srliw" => Some(sext32((a32 >> (shamt & 0x1f)) as u64)),
+        "sraiw" => Some(((a32 as i32) >> (shamt & 0x1f)) as i64 as u64),
+        "addw" => Some(sext32(a.wrapping_add(b))),
+        "subw" => Some(sext32(a.wrapping_sub(b))),
+        "sllw" => Some(sext32((a32 << (b & 0x1f)) as u64)),
+        "srlw" => Some(sext32((a32 >> (b & 0x1f)) as u64)),
+        "sraw" => Some(((a32 as i32) >> (b & 0x1f)) as i64 as u64),
+        "mul" => Some(a.wrapping_mul(b)),
+        "mulh" => Some(((a as i64 as i128 * b as i64 as i128) >> 64) as u64),
+        "mulhsu" => Some(((a as i64 as i128).wrapping_mul(b as i128) >> 64) as u64),
+        "mulhu" => Some(((a as u128 * b as u128) >> 64) as u64),
+        // division by zero and overflow don't trap in RISC-V
+        "div" if b == 0 => Some(u64::MAX),
+        "div" => Some((a as i64).wrapping_div(b as i64) as u64),
+        "divu" => Some(a.checked_div(b).unwrap_or(u64::MAX)),
+        "rem" if b == 0 => Some(a),
+        "rem" => Some((a as i64).wrapping_rem(b as i64) as u64),
+        "remu" => Some(a.checked_rem(b).unwrap_or(a)),
+        "mulw" => Some(sext32(a.wrapping_mul(b))),
+        "divw" if b32 == 0 => Some(u64::MAX),
+        "divw" => Some((a32 as i32).wrapping_div(b32 as i32) as i64 as u64),
+        "divuw" => Some(a32.checked_div(b32).map_or(u64::MAX, |q| sext32(q as u64))),
+        "remw" if b32 == 0 => Some(sext32(a)),
+        "remw" => Some((a32 as i32).wrapping_rem(b32 as i32) as i64 as u64),
+        "remuw" => Some(sext32(a32.checked_rem(b32).unwrap_or(a32) as u64)),
+        _ => None,
+    };
+    let mem_addr = match instr & 0x7f {
+        opcode::LOAD | opcode::STORE => Some(a.wrapping_add(imm)),
+        opcode::AMO => Some(a),
+        _ => None,
+    };
+    let next_pc = match def.format {
+        InstrFormat::J => Some(pc.wrapping_add(imm)),
+        InstrFormat::B => Some(if branch_taken(def, a, b) {
+            pc.wrapping_add(imm)
+        } else {
+            pc.wrapping_add(len)
+        }),
+        _ if def.name == "jalr" => Some(a.wrapping_add(imm) & !1),
+        _ => None,
+    };
+    Preview {
+        // writes to x0 are ignored
+        result: result.filter(|_| fields.rd != Some(0)),
+        mem_addr,
+        next_pc,
+    }
+}
+
+/// Names of the common machine, supervisor and user CSRs
+pub const CSR_NAMES: &[(u32, &str)] = &[
+    (0x001, "fflags"),
+    (0x002, "frm"),
+    (0x003, "fcsr"),
+    (0x100, "sstatus"),
+    (0x104, "sie"),
+    (0x105, "stvec"),
+    (0x106, "scounteren"),
+    (0x140, "sscratch"),
+    (0x141, "sepc"),
+    (0x142, "scause"),
+    (0x143, "stval"),
+    (0x144, "sip"),
+    (0x180, "satp"),
+    (0x300, "mstatus"),
+    (0x301, "misa"),
+    (0x302, "medeleg"),
+    (0x303, "mideleg"),
+    (0x304, "mie"),
+    (0x305, "mtvec"),
+    (0x306, "mcounteren"),
+    (0x340, "mscratch"),
+    (0x341, "mepc"),
+    (0x342, "mcause"),
+    (0x343, "mtval"),
+    (0x344, "mip"),
+    (0xb00, "mcycle"),
+    (0xb02, "minstret"),
+    (0xc00, "cycle"),
+    (0xc01, "time"),
+    (0xc02, "instret"),
+    (0xf11, "mvendorid"),
+    (0xf12, "marchid"),
+    (0xf13, "mimpid"),
+    (0xf14, "mhartid"),
+];
+
+pub fn csr_name(csr: u32) -> Option<&'static str> {
+    CSR_NAMES
+        .iter()
+        .find(|(addr, _)| *addr == csr)
+        .map(|(_, name)| *name)
+}
@@ -0,0 +1,68 @@
+use crate::isa::{Extension, INSTRS, OTHER_EXTENSIONS};
+
+/// Extensions and instructions the decoder and the simulator support
+#[derive(serde::Deserialize, serde::Serialize, Default)]
+#[serde(default)]
+pub struct IsaReference {
+    /// Is window open or not
+    window_open: bool,
+}
+
+impl IsaReference {
+    pub fn open(&mut self) {
+        self.window_open = true;
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) {
+        let mut open = self.window_open;
+        egui::Window::new("ISA reference")
+            .open(&mut open)
+            .resizable(true)
+            .default_width(500.0)
+            .show(ctx, |ui| {
+                ui.label(
+                    "The simulator runs RV64I and Zicsr. Instructions of other \
+                     extensions raise Illegal instruction exception.",
+                );
+                ui.separator();
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::Grid::new("isa_reference_grid")
+                        .num_columns(3)
+                        .spacing([20.0, 4.0])
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.strong("Extension");
+                            ui.strong("Simulator");
+                            ui.strong("Instructions");
+                            ui.end_row();
+                            for ext in Extension::ALL {
+                                ui.label(ext.name());
+                                support_label(ui, ext.simulated());
+                                let names: Vec<&str> = INSTRS
+                                    .iter()
+                                    .filter(|def| def.ext == ext)
+                                    .map(|def| def.name)
+                                    .collect();
+                                ui.add(egui::Label::new(names.join(" ")).wrap(true));
+                                ui.end_row();
+                            }
+                            for (name, description) in OTHER_EXTENSIONS {
+                                ui.label(*name);
+                                support_label(ui, false);
+                                ui.label(*description);
+                                ui.end_row();
+                            }
+                        });
+                });
+            });
+        self.window_open = open;
+    }
+}
+
+fn support_label(ui: &mut egui::Ui, simulated: bool) {
+    if simulated {
+        ui.label("yes");
+    } else {
+        ui.colored_label(ui.visuals().warn_fg_color, "no");
+    }
+}
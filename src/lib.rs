@@ -1,9 +1,27 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
+mod annotations;
 mod app;
+mod assembler;
 pub use app::KompusimApp;
+mod branch_arrows;
+mod breakpoints;
 mod console;
+mod device_tree;
+mod elf;
+mod fdt;
+mod hex_dump;
 mod instr_decoder;
+mod instr_format;
 mod instr_list;
+mod instr_search;
+mod isa;
+mod isa_reference;
+mod listing;
+mod load_binary;
 mod load_demo;
+mod number;
+mod regions;
+mod regs;
+mod rvc;
 mod sim;
@@ -0,0 +1,93 @@
+//! Disassembly listing in a text format close to `objdump -d`
+//! so that it can be diffed against the toolchain output
+
+use kompusim::rv64i_disasm::disasm;
+
+use crate::{
+    regions::{data_directive, RegionKind},
+    rvc::{expand, is_compressed},
+};
+
+/// What to do with an exported listing
+#[derive(Clone, Copy)]
+pub enum ExportAction {
+    #[cfg(not(target_arch = "wasm32"))]
+    Save,
+    Copy,
+}
+
+/// Header of the listing
+pub fn listing_header(image_name: &str) -> String {
+    format!("\n{image_name}:     file format binary\n\n\nDisassembly of section .text:\n")
+}
+
+fn push_label(out: &mut String, addr: u64, label: Option<&str>) {
+    if let Some(label) = label {
+        out.push_str(&format!("\n{addr:016x} <{label}>:\n"));
+    }
+}
+
+fn push_comment(line: &mut String, comment: Option<&str>) {
+    if let Some(comment) = comment {
+        line.push_str(&format!("\t# {comment}"));
+    }
+}
+
+/// Append the instruction line, preceded by its label if any
+pub fn listing_line(
+    out: &mut String,
+    addr: u64,
+    instr: u32,
+    label: Option<&str>,
+    comment: Option<&str>,
+) {
+    push_label(out, addr, label);
+    let encoding = if is_compressed(instr) {
+        format!("{instr:04x}")
+    } else {
+        format!("{instr:08x}")
+    };
+    // objdump separates the mnemonic with a tab and operands only with commas
+    let disasm = disasm(expand(instr), addr);
+    let (mnemonic, operands) = match disasm.trim().split_once(char::is_whitespace) {
+        Some((mnemonic, operands)) => (mnemonic.to_string(), operands.replace(' ', "")),
+        None => (disasm.trim().to_string(), String::new()),
+    };
+    let mut line = format!("{addr:>12x}:\t{encoding:<20}\t{mnemonic}");
+    if !operands.is_empty() {
+        line.push('\t');
+        line.push_str(&operands);
+    }
+    push_comment(&mut line, comment);
+    out.push_str(line.trim_end());
+    out.push('\n');
+}
+
+/// Append the data line as an assembler directive, preceded by its label if any
+pub fn listing_data_line(
+    out: &mut String,
+    addr: u64,
+    kind: RegionKind,
+    bytes: &[u8],
+    label: Option<&str>,
+    comment: Option<&str>,
+) {
+    push_label(out, addr, label);
+    let mut line = format!("{addr:>12x}:\t{}", data_directive(kind, bytes));
+    push_comment(&mut line, comment);
+    out.push_str(&line);
+    out.push('\n');
+}
+
+/// Ask for a file name and write the listing, returns the status to show
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_listing(image_name: &str, text: &str) -> Option<String> {
+    let path = rfd::FileDialog::new()
+        .set_file_name(&format!("{image_name}.txt"))
+        .add_filter("Text", &["txt"])
+        .save_file()?;
+    Some(match std::fs::write(&path, text) {
+        Ok(()) => format!("saved to {}", path.display()),
+        Err(err) => format!("failed to save: {err}"),
+    })
+}
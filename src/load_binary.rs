@@ -0,0 +1,155 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// A program image picked by the user or dropped onto the window
+pub struct Binary {
+    pub name: String,
+    pub bytes: Vec<u8>,
+    /// Path on disk (native only)
+    pub path: Option<PathBuf>,
+}
+
+/// Loads raw binaries from the file picker or from files dropped onto the window.
+/// On the web the file picker is asynchronous, so the picked file is received
+/// through a channel on one of the following frames.
+pub struct BinaryLoader {
+    picked_send: Sender<Binary>,
+    picked_recv: Receiver<Binary>,
+}
+
+impl Default for BinaryLoader {
+    fn default() -> BinaryLoader {
+        let (picked_send, picked_recv) = mpsc::channel();
+        BinaryLoader {
+            picked_send,
+            picked_recv,
+        }
+    }
+}
+
+impl BinaryLoader {
+    /// Open the file picker
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn pick_file(&mut self, _ctx: &egui::Context) {
+        if let Some(path) = rfd::FileDialog::new().pick_file() {
+            match read_binary(path) {
+                Ok(binary) => self.picked_send.send(binary).unwrap(),
+                Err(err) => println!("Failed to load binary: {}", err),
+            }
+        }
+    }
+
+    /// Open the browser file picker
+    #[cfg(target_arch = "wasm32")]
+    pub fn pick_file(&mut self, ctx: &egui::Context) {
+        let picked_send = self.picked_send.clone();
+        let ctx = ctx.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            if let Some(file) = rfd::AsyncFileDialog::new().pick_file().await {
+                let binary = Binary {
+                    name: file.file_name(),
+                    bytes: file.read().await,
+                    path: None,
+                };
+                if picked_send.send(binary).is_ok() {
+                    ctx.request_repaint();
+                }
+            }
+        });
+    }
+
+    /// Returns a picked or dropped binary if there is one
+    pub fn poll(&mut self, ctx: &egui::Context) -> Option<Binary> {
+        match self.picked_recv.try_recv() {
+            Ok(binary) => return Some(binary),
+            Err(TryRecvError::Empty) => {}
+            Err(TryRecvError::Disconnected) => unreachable!("the loader owns the sender"),
+        }
+        let dropped_file = ctx.input(|i| i.raw.dropped_files.first().cloned())?;
+        if let Some(bytes) = dropped_file.bytes {
+            // web: the browser gives us the content of the file
+            Some(Binary {
+                name: dropped_file.name,
+                bytes: bytes.to_vec(),
+                path: None,
+            })
+        } else if let Some(path) = dropped_file.path {
+            match read_binary(path) {
+                Ok(binary) => Some(binary),
+                Err(err) => {
+                    println!("Failed to load dropped binary: {}", err);
+                    None
+                }
+            }
+        } else {
+            None
+        }
+    }
+}
+
+pub fn read_binary(path: PathBuf) -> std::io::Result<Binary> {
+    Ok(Binary {
+        name: path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        bytes: std::fs::read(&path)?,
+        path: Some(path),
+    })
+}
+
+/// Polls modification time of a file on disk (native only)
+pub struct FileWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    /// Modification time seen on the previous poll which is not reported yet.
+    /// A change is reported once the file stops changing, so a half-written
+    /// file from a running build isn't picked up.
+    pending: Option<SystemTime>,
+    last_poll: Instant,
+}
+
+impl FileWatcher {
+    pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+    pub fn new(path: PathBuf) -> FileWatcher {
+        FileWatcher {
+            modified: modified_time(&path),
+            path,
+            pending: None,
+            last_poll: Instant::now(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns true if the file has changed since the last reported change
+    pub fn changed(&mut self) -> bool {
+        if self.last_poll.elapsed() < FileWatcher::POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+        let modified = modified_time(&self.path);
+        if modified.is_none() || modified == self.modified {
+            // the file can be missing while the build rewrites it
+            self.pending = None;
+            return false;
+        }
+        if self.pending != modified {
+            self.pending = modified;
+            return false;
+        }
+        self.modified = modified;
+        self.pending = None;
+        true
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
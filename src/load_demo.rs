@@ -1,16 +1,228 @@
+use crate::sim::MachineConfig;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum DemoCategory {
+    BareMetal,
+    Basics,
+}
+
+impl DemoCategory {
+    fn name(&self) -> &'static str {
+        match self {
+            DemoCategory::BareMetal => "Bare metal",
+            DemoCategory::Basics => "Instruction basics",
+        }
+    }
+}
+
+/// Windows a demo suggests to open after it is loaded
+#[derive(Clone, Copy, PartialEq)]
+pub enum DemoWindow {
+    InstrList,
+    InstrDecoder,
+    Console,
+}
+
+/// An embedded program together with everything needed to run it
+pub struct Demo {
+    /// Short name used in URLs (e.g., ?demo=uart_hello_world)
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub category: DemoCategory,
+    pub image: &'static [u8],
+    pub load_addr: u64,
+    pub entry: u64,
+    pub machine: MachineConfig,
+    pub windows: &'static [DemoWindow],
+}
+
+const RAM_BASE: u64 = MachineConfig::DEFAULT.ram_base;
+
+/// The default machine with 8 KiB of RAM and
+/// the device tree in the upper 4 KiB of RAM
+const SMALL_MACHINE: MachineConfig = MachineConfig {
+    ram_size: 8 * 1024,
+    dtb_addr: Some(RAM_BASE + 4 * 1024),
+    ..MachineConfig::DEFAULT
+};
+
+/// Sources of the demos are next to the binaries in assets/test_bins/
+const DEMOS: &[Demo] = &[
+    Demo {
+        id: "uart_hello_world",
+        name: "Hello world with UART",
+        description: "Prints \"Hello, World!\" to the UART",
+        category: DemoCategory::BareMetal,
+        image: include_bytes!("../assets/test_bins/uart_hello_world.bin"),
+        load_addr: RAM_BASE,
+        entry: RAM_BASE,
+        machine: SMALL_MACHINE,
+        windows: &[DemoWindow::Console, DemoWindow::InstrList],
+    },
+    Demo {
+        id: "uart_hex",
+        name: "Hex number with UART",
+        description: "Prints a 64-bit number in hexadecimal to the UART",
+        category: DemoCategory::BareMetal,
+        image: include_bytes!("../assets/test_bins/uart_hex.bin"),
+        load_addr: RAM_BASE,
+        entry: RAM_BASE,
+        machine: SMALL_MACHINE,
+        windows: &[DemoWindow::Console, DemoWindow::InstrList],
+    },
+    Demo {
+        id: "arithmetic",
+        name: "Arithmetic",
+        description: "Integer arithmetic, logic and shift instructions",
+        category: DemoCategory::Basics,
+        image: include_bytes!("../assets/test_bins/arithmetic.bin"),
+        load_addr: RAM_BASE,
+        entry: RAM_BASE,
+        machine: SMALL_MACHINE,
+        windows: &[DemoWindow::InstrList, DemoWindow::InstrDecoder],
+    },
+    Demo {
+        id: "loop",
+        name: "Loop",
+        description: "Sum of 1..10 with a conditional branch",
+        category: DemoCategory::Basics,
+        image: include_bytes!("../assets/test_bins/loop.bin"),
+        load_addr: RAM_BASE,
+        entry: RAM_BASE,
+        machine: SMALL_MACHINE,
+        windows: &[DemoWindow::InstrList],
+    },
+    Demo {
+        id: "function_calls",
+        name: "Function calls",
+        description: "call/ret with the return address saved on the stack",
+        category: DemoCategory::Basics,
+        image: include_bytes!("../assets/test_bins/function_calls.bin"),
+        load_addr: RAM_BASE,
+        entry: RAM_BASE,
+        machine: SMALL_MACHINE,
+        windows: &[DemoWindow::InstrList],
+    },
+    Demo {
+        id: "load_store",
+        name: "Loads and stores",
+        description: "Memory accesses of different widths, sign and zero extension",
+        category: DemoCategory::Basics,
+        image: include_bytes!("../assets/test_bins/load_store.bin"),
+        load_addr: RAM_BASE,
+        entry: RAM_BASE,
+        machine: SMALL_MACHINE,
+        windows: &[DemoWindow::InstrList, DemoWindow::InstrDecoder],
+    },
+    Demo {
+        id: "fibonacci",
+        name: "Fibonacci numbers",
+        description: "fib(20) computed iteratively in registers",
+        category: DemoCategory::Basics,
+        image: include_bytes!("../assets/test_bins/fibonacci.bin"),
+        load_addr: RAM_BASE,
+        entry: RAM_BASE,
+        machine: SMALL_MACHINE,
+        windows: &[DemoWindow::InstrList],
+    },
+    Demo {
+        id: "gcd",
+        name: "Greatest common divisor",
+        description: "Euclid's algorithm with subtraction and unsigned comparison",
+        category: DemoCategory::Basics,
+        image: include_bytes!("../assets/test_bins/gcd.bin"),
+        load_addr: RAM_BASE,
+        entry: RAM_BASE,
+        machine: SMALL_MACHINE,
+        windows: &[DemoWindow::InstrList],
+    },
+    Demo {
+        id: "bit_count",
+        name: "Counting bits",
+        description: "Shifts and masks, two ways to count set bits",
+        category: DemoCategory::Basics,
+        image: include_bytes!("../assets/test_bins/bit_count.bin"),
+        load_addr: RAM_BASE,
+        entry: RAM_BASE,
+        machine: SMALL_MACHINE,
+        windows: &[DemoWindow::InstrList, DemoWindow::InstrDecoder],
+    },
+    Demo {
+        id: "factorial",
+        name: "Recursive factorial",
+        description: "Recursion with stack frames and multiplication by shifts",
+        category: DemoCategory::Basics,
+        image: include_bytes!("../assets/test_bins/factorial.bin"),
+        load_addr: RAM_BASE,
+        entry: RAM_BASE,
+        machine: SMALL_MACHINE,
+        windows: &[DemoWindow::InstrList],
+    },
+    Demo {
+        id: "memcpy",
+        name: "Copying memory",
+        description: "Byte loads and stores copying a string to a buffer",
+        category: DemoCategory::Basics,
+        image: include_bytes!("../assets/test_bins/memcpy.bin"),
+        load_addr: RAM_BASE,
+        entry: RAM_BASE,
+        machine: SMALL_MACHINE,
+        windows: &[DemoWindow::InstrList],
+    },
+    Demo {
+        id: "bubble_sort",
+        name: "Bubble sort",
+        description: "Sorting signed double words in memory",
+        category: DemoCategory::Basics,
+        image: include_bytes!("../assets/test_bins/bubble_sort.bin"),
+        load_addr: RAM_BASE,
+        entry: RAM_BASE,
+        machine: SMALL_MACHINE,
+        windows: &[DemoWindow::InstrList],
+    },
+];
+
+/// A demo which needs features the simulator doesn't have yet,
+/// listed in the catalog as unimplemented
+struct PlannedDemo {
+    name: &'static str,
+    /// What the demo will show
+    description: &'static str,
+    /// What it is waiting for
+    missing: &'static str,
+}
+
+const PLANNED_DEMOS: &[PlannedDemo] = &[
+    PlannedDemo {
+        name: "Traps",
+        description: "ecall and exception handlers installed in mtvec",
+        missing: "trap handling (mtvec, mepc, mcause) in the simulated CPU",
+    },
+    PlannedDemo {
+        name: "Timer interrupt",
+        description: "Periodic machine timer interrupts programmed through mtimecmp",
+        missing: "a CLINT device and interrupts in the simulated CPU",
+    },
+    PlannedDemo {
+        name: "Linux kernel",
+        description: "OpenSBI, the kernel and an initramfs booting to a shell on the console",
+        missing: "CLINT and PLIC devices, traps and interrupts in the simulated CPU, \
+                  bundled firmware, kernel and initramfs images",
+    },
+];
+
 pub struct LoadDemo {
     /// Is window open or not
     window_open: bool,
-    test_bin1: &'static [u8],
+    demos: &'static [Demo],
 }
 
 impl Default for LoadDemo {
     fn default() -> LoadDemo {
-        let bare_metal_uart_hello_world =
-            include_bytes!("../assets/test_bins/uart_hello_world.bin");
         LoadDemo {
             window_open: true,
-            test_bin1: bare_metal_uart_hello_world,
+            demos: DEMOS,
         }
     }
 }
@@ -20,41 +232,60 @@ impl LoadDemo {
         self.window_open = true;
     }
 
-    pub fn show(&mut self, ctx: &egui::Context) -> Option<&'static [u8]> {
-        let mut chose_demo1 = false;
+    pub fn close(&mut self) {
+        self.window_open = false;
+    }
+
+    pub fn find(&self, id: &str) -> Option<&'static Demo> {
+        self.demos.iter().find(|demo| demo.id == id)
+    }
+
+    /// Returns the demo the user chose to load
+    pub fn show(&mut self, ctx: &egui::Context) -> Option<&'static Demo> {
+        let mut chosen_demo = None;
         if self.window_open {
             let mut window_opened = self.window_open;
+            let demos = self.demos;
             egui::Window::new("Load demo")
                 .open(&mut window_opened)
                 .resizable(true)
                 .default_width(400.0)
                 .show(ctx, |ui| {
                     egui::Grid::new("load_demo_grid")
-                        .num_columns(2)
+                        .num_columns(3)
                         .spacing([40.0, 4.0])
                         .striped(true)
                         .show(ui, |ui| {
-                            ui.label("Bare metal hello world with UART");
-                            if ui.button("Load").clicked() {
-                                self.window_open = false;
-                                chose_demo1 = true;
+                            let mut category = None;
+                            for demo in demos {
+                                if category != Some(demo.category) {
+                                    category = Some(demo.category);
+                                    ui.strong(demo.category.name());
+                                    ui.end_row();
+                                }
+                                ui.label(demo.name);
+                                ui.label(demo.description);
+                                if ui.button("Load").clicked() {
+                                    self.window_open = false;
+                                    chosen_demo = Some(demo);
+                                }
+                                ui.end_row();
                             }
+                            ui.strong("Not implemented yet");
                             ui.end_row();
-                            ui.label("Linux kernel (unimplemented)");
-                            if ui.button("Load").clicked() {
-                                self.window_open = false;
+                            for demo in PLANNED_DEMOS {
+                                ui.label(format!("{} (unimplemented)", demo.name));
+                                ui.label(demo.description);
+                                ui.add_enabled(false, egui::Button::new("Load"))
+                                    .on_disabled_hover_text(format!("Needs {}", demo.missing));
+                                ui.end_row();
                             }
-                            ui.end_row();
                         });
                 });
             if self.window_open {
                 self.window_open = window_opened;
             }
         }
-        if chose_demo1 {
-            Some(self.test_bin1)
-        } else {
-            None
-        }
+        chosen_demo
     }
 }
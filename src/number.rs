@@ -0,0 +1,126 @@
+//! Parsing of numbers typed by the user
+
+/// Integer in decimal, hex (0x) or binary (0b), optionally negative.
+/// Hex and binary take all 64 bits, e.g., 0xffffffffffffffff is -1.
+/// Underscores are ignored.
+pub fn parse_int(text: &str) -> Option<i64> {
+    let text = text.trim().to_lowercase().replace('_', "");
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text.as_str()),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        u64::from_str_radix(hex, 16).ok()? as i64
+    } else if let Some(bin) = digits.strip_prefix("0b") {
+        u64::from_str_radix(bin, 2).ok()? as i64
+    } else {
+        digits.parse::<u64>().ok()?.try_into().ok()?
+    };
+    Some(if negative {
+        value.wrapping_neg()
+    } else {
+        value
+    })
+}
+
+/// Address in hex with or without 0x. Underscores are ignored.
+pub fn parse_addr(text: &str) -> Option<u64> {
+    let text = text.trim().to_lowercase().replace('_', "");
+    let digits = text.strip_prefix("0x").unwrap_or(&text);
+    if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    u64::from_str_radix(digits, 16).ok()
+}
+
+/// Instruction encoding: hex with or without 0x, binary (0b), decimal (0d)
+/// or 2 or 4 bytes of a little-endian dump in memory order, e.g., "13 05 15 00"
+/// or "[0x13,0x05,0x15,0x00]". Underscores are ignored.
+pub fn parse_instr(text: &str) -> Result<u32, String> {
+    let text = text.trim().trim_start_matches('[').trim_end_matches(']');
+    let text = text.trim().to_lowercase().replace('_', "");
+    if text.is_empty() {
+        return Err("enter an instruction".to_string());
+    }
+    let bytes: Vec<&str> = text
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|byte| !byte.is_empty())
+        .collect();
+    if bytes.len() > 1 {
+        if bytes.len() != 2 && bytes.len() != 4 {
+            return Err(format!("expected 2 or 4 bytes, got {}", bytes.len()));
+        }
+        return bytes.iter().enumerate().try_fold(0, |value, (i, byte)| {
+            let digits = byte.strip_prefix("0x").unwrap_or(byte);
+            match u8::from_str_radix(digits, 16) {
+                Ok(byte) if digits.len() <= 2 => Ok(value | (byte as u32) << (8 * i)),
+                _ => Err(format!("invalid byte '{byte}'")),
+            }
+        });
+    }
+
+    // hex numbers can start with 0b or 0d too
+    let is_radix =
+        |digits: &str, radix| !digits.is_empty() && digits.chars().all(|c| c.is_digit(radix));
+    let (digits, radix, what) = match (text.strip_prefix("0b"), text.strip_prefix("0d")) {
+        (Some(bin), _) if is_radix(bin, 2) => (bin, 2, "binary"),
+        (_, Some(dec)) if is_radix(dec, 10) => (dec, 10, "decimal"),
+        _ => (text.strip_prefix("0x").unwrap_or(&text), 16, "hex"),
+    };
+    if let Some(c) = digits.chars().find(|c| !c.is_digit(radix)) {
+        return Err(format!("invalid {what} digit '{c}'"));
+    }
+    if digits.is_empty() {
+        return Err(format!("missing {what} digits"));
+    }
+    u32::from_str_radix(digits, radix)
+        .map_err(|_| format!("{what} {digits} doesn't fit in 32 bits"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integers() {
+        assert_eq!(parse_int("42"), Some(42));
+        assert_eq!(parse_int("-0x10"), Some(-16));
+        assert_eq!(parse_int("0b101"), Some(5));
+        assert_eq!(parse_int("1_000"), Some(1000));
+        assert_eq!(parse_int("0xffff_ffff_ffff_ffff"), Some(-1));
+        assert_eq!(parse_int("-9223372036854775808"), None);
+        assert_eq!(parse_int("0x"), None);
+        assert_eq!(parse_int("a0"), None);
+    }
+
+    #[test]
+    fn addresses() {
+        assert_eq!(parse_addr("80000000"), Some(0x8000_0000));
+        assert_eq!(parse_addr(" 0x8000_0000 "), Some(0x8000_0000));
+        assert_eq!(parse_addr("0X8000_0000"), Some(0x8000_0000));
+        assert_eq!(parse_addr("+10"), None);
+        assert_eq!(parse_addr("0x"), None);
+        assert_eq!(parse_addr("pc"), None);
+    }
+
+    #[test]
+    fn instructions() {
+        assert_eq!(parse_instr("00150513"), Ok(0x0015_0513));
+        assert_eq!(parse_instr("0x0015_0513"), Ok(0x0015_0513));
+        assert_eq!(
+            parse_instr("0b0000_0000_0001_0101_0000_0101_0001_0011"),
+            Ok(0x0015_0513)
+        );
+        assert_eq!(parse_instr("0d1377555"), Ok(0x0015_0513));
+        assert_eq!(parse_instr("0505"), Ok(0x0505));
+    }
+
+    #[test]
+    fn bytes_in_memory_order() {
+        assert_eq!(parse_instr("13 05 15 00"), Ok(0x0015_0513));
+        assert_eq!(parse_instr("[0x13, 0x05, 0x15, 0x00]"), Ok(0x0015_0513));
+        assert_eq!(parse_instr("05 05"), Ok(0x0505));
+        assert!(parse_instr("13 05 15").is_err());
+        assert!(parse_instr("13 05 15 100").is_err());
+    }
+}
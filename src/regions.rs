@@ -0,0 +1,250 @@
+//! Code and data regions of an image. Data regions are shown as
+//! assembler directives instead of disassembled garbage.
+
+use crate::elf::{Section, SHF_EXECINSTR, SHF_STRINGS};
+
+#[derive(Clone, Copy, PartialEq, Debug, serde::Deserialize, serde::Serialize)]
+pub enum RegionKind {
+    Code,
+    Bytes,
+    Words,
+    Ascii,
+    Pointers,
+}
+
+impl RegionKind {
+    pub const ALL: [RegionKind; 5] = [
+        RegionKind::Code,
+        RegionKind::Bytes,
+        RegionKind::Words,
+        RegionKind::Ascii,
+        RegionKind::Pointers,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RegionKind::Code => "Code",
+            RegionKind::Bytes => "Bytes",
+            RegionKind::Words => "Words",
+            RegionKind::Ascii => "ASCII strings",
+            RegionKind::Pointers => "Pointers",
+        }
+    }
+}
+
+/// Address range [start, end)
+#[derive(Clone, Copy, serde::Deserialize, serde::Serialize)]
+pub struct Region {
+    pub start: u64,
+    pub end: u64,
+    pub kind: RegionKind,
+}
+
+/// Non-overlapping regions sorted by address
+#[derive(Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Regions {
+    list: Vec<Region>,
+}
+
+impl Regions {
+    /// Regions of the allocated ELF sections which are not executable
+    pub fn from_sections(sections: &[Section]) -> Regions {
+        let mut regions = Regions::default();
+        for section in sections {
+            if section.flags & SHF_EXECINSTR != 0 {
+                continue;
+            }
+            let kind =
+                if section.flags & SHF_STRINGS != 0 || section.name.starts_with(".rodata.str") {
+                    RegionKind::Ascii
+                } else if section.nobits {
+                    RegionKind::Bytes
+                } else {
+                    RegionKind::Words
+                };
+            regions.set(section.addr, section.addr + section.size, kind);
+        }
+        regions
+    }
+
+    pub fn list(&self) -> &[Region] {
+        &self.list
+    }
+
+    /// Mark [start, end), overlapping parts of other regions are replaced
+    pub fn set(&mut self, start: u64, end: u64, kind: RegionKind) {
+        if start >= end {
+            return;
+        }
+        let mut list = Vec::with_capacity(self.list.len() + 2);
+        for region in &self.list {
+            if region.end <= start || region.start >= end {
+                list.push(*region);
+                continue;
+            }
+            if region.start < start {
+                list.push(Region {
+                    end: start,
+                    ..*region
+                });
+            }
+            if region.end > end {
+                list.push(Region {
+                    start: end,
+                    ..*region
+                });
+            }
+        }
+        list.push(Region { start, end, kind });
+        list.sort_by_key(|region| region.start);
+        self.list = list;
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if index < self.list.len() {
+            self.list.remove(index);
+        }
+    }
+
+    /// Region containing the address
+    pub fn find(&self, addr: u64) -> Option<&Region> {
+        let i = self.list.partition_point(|region| region.start <= addr);
+        self.list[..i].last().filter(|region| addr < region.end)
+    }
+
+    /// Start of the first region after the address
+    pub fn next_start(&self, addr: u64) -> Option<u64> {
+        let i = self.list.partition_point(|region| region.start <= addr);
+        self.list.get(i).map(|region| region.start)
+    }
+}
+
+/// Longest string shown in one row
+pub const MAX_STRING_LEN: u64 = 64;
+
+/// Length in bytes of a data row starting at the address,
+/// `bytes` - memory from the address to the end of the region (or less)
+pub fn data_row_len(kind: RegionKind, bytes: &[u8]) -> u64 {
+    let len = match kind {
+        RegionKind::Code | RegionKind::Bytes | RegionKind::Words => 4,
+        RegionKind::Pointers => 8,
+        RegionKind::Ascii => bytes
+            .iter()
+            .take(MAX_STRING_LEN as usize)
+            .position(|b| *b == 0)
+            .map_or(MAX_STRING_LEN, |nul| nul as u64 + 1),
+    };
+    len.min(bytes.len() as u64).max(1)
+}
+
+/// Assembler directive for the data, e.g., ".word 0x12345678"
+pub fn data_directive(kind: RegionKind, bytes: &[u8]) -> String {
+    match (kind, bytes.len()) {
+        (RegionKind::Words, 4) => {
+            let word = u32::from_le_bytes(bytes.try_into().unwrap());
+            format!(".word 0x{word:08x}")
+        }
+        (RegionKind::Pointers, 8) => {
+            let dword = u64::from_le_bytes(bytes.try_into().unwrap());
+            format!(".dword 0x{dword:016x}")
+        }
+        (RegionKind::Ascii, _) => {
+            let (text, directive) = match bytes.split_last() {
+                Some((0, text)) => (text, ".asciz"),
+                _ => (bytes, ".ascii"),
+            };
+            format!("{directive} \"{}\"", text.escape_ascii())
+        }
+        // bytes and incomplete words or pointers at the end of the region
+        _ => {
+            let bytes: Vec<String> = bytes.iter().map(|b| format!("0x{b:02x}")).collect();
+            format!(".byte {}", bytes.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spans(regions: &Regions) -> Vec<(u64, u64, RegionKind)> {
+        regions
+            .list()
+            .iter()
+            .map(|r| (r.start, r.end, r.kind))
+            .collect()
+    }
+
+    fn section(name: &str, addr: u64, size: u64, flags: u64, nobits: bool) -> Section {
+        Section {
+            name: name.to_string(),
+            addr,
+            size,
+            flags,
+            nobits,
+        }
+    }
+
+    #[test]
+    fn from_sections() {
+        let regions = Regions::from_sections(&[
+            section(".text", 0x0, 0x100, SHF_EXECINSTR, false),
+            section(".rodata.str1.1", 0x100, 0x20, 0, false),
+            section(".rodata", 0x120, 0x10, 0, false),
+            section(".comment", 0x130, 0x10, SHF_STRINGS, false),
+            section(".bss", 0x140, 0x40, 0, true),
+        ]);
+        assert_eq!(
+            spans(&regions),
+            vec![
+                (0x100, 0x120, RegionKind::Ascii),
+                (0x120, 0x130, RegionKind::Words),
+                (0x130, 0x140, RegionKind::Ascii),
+                (0x140, 0x180, RegionKind::Bytes),
+            ]
+        );
+    }
+
+    #[test]
+    fn set_replaces_overlapping_parts() {
+        let mut regions = Regions::default();
+        regions.set(0x10, 0x40, RegionKind::Words);
+        regions.set(0x20, 0x30, RegionKind::Ascii);
+        regions.set(0x38, 0x48, RegionKind::Pointers);
+        regions.set(0x50, 0x50, RegionKind::Bytes);
+        assert_eq!(
+            spans(&regions),
+            vec![
+                (0x10, 0x20, RegionKind::Words),
+                (0x20, 0x30, RegionKind::Ascii),
+                (0x30, 0x38, RegionKind::Words),
+                (0x38, 0x48, RegionKind::Pointers),
+            ]
+        );
+        assert_eq!(regions.find(0x2f).map(|r| r.kind), Some(RegionKind::Ascii));
+        assert!(regions.find(0x48).is_none());
+        assert_eq!(regions.next_start(0x21), Some(0x30));
+        assert_eq!(regions.next_start(0x40), None);
+    }
+
+    #[test]
+    fn data_rows() {
+        assert_eq!(data_row_len(RegionKind::Ascii, b"hi\0there\0"), 3);
+        assert_eq!(data_row_len(RegionKind::Ascii, b"no nul"), 6);
+        assert_eq!(data_row_len(RegionKind::Words, &[1, 2]), 2);
+        assert_eq!(data_row_len(RegionKind::Pointers, &[0; 16]), 8);
+        assert_eq!(
+            data_directive(RegionKind::Ascii, b"hi\n\0"),
+            ".asciz \"hi\\n\""
+        );
+        assert_eq!(
+            data_directive(RegionKind::Words, &[0x78, 0x56, 0x34, 0x12]),
+            ".word 0x12345678"
+        );
+        assert_eq!(
+            data_directive(RegionKind::Words, &[1, 2]),
+            ".byte 0x01, 0x02"
+        );
+    }
+}
@@ -0,0 +1,21 @@
+/// ABI names of the integer registers x0..x31
+pub const ABI_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4",
+    "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+    "t5", "t6",
+];
+
+/// Parse register name: ABI name (e.g., "a0"), "fp" or x-name (e.g., "x10")
+pub fn parse_reg(name: &str) -> Option<usize> {
+    let name = name.trim();
+    if name == "fp" {
+        return Some(8);
+    }
+    if let Some(i) = ABI_NAMES.iter().position(|abi_name| *abi_name == name) {
+        return Some(i);
+    }
+    match name.strip_prefix('x')?.parse::<usize>() {
+        Ok(i) if i < 32 => Some(i),
+        _ => None,
+    }
+}
@@ -0,0 +1,412 @@
+//! Compressed (RVC) instructions of RV64C: formats and expansion
+//! to the equivalent 32-bit instructions
+
+use crate::instr_format::{bits, opcode, sext};
+
+/// Formats of 16-bit instructions
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CFormat {
+    Cr,
+    Ci,
+    Css,
+    Ciw,
+    Cl,
+    Cs,
+    Ca,
+    Cb,
+    Cj,
+}
+
+impl CFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            CFormat::Cr => "CR (register)",
+            CFormat::Ci => "CI (immediate)",
+            CFormat::Css => "CSS (stack-relative store)",
+            CFormat::Ciw => "CIW (wide immediate)",
+            CFormat::Cl => "CL (load)",
+            CFormat::Cs => "CS (store)",
+            CFormat::Ca => "CA (arithmetic)",
+            CFormat::Cb => "CB (branch/arithmetic)",
+            CFormat::Cj => "CJ (jump)",
+        }
+    }
+
+    /// Fields of the format from the most significant bits: (name, hi, lo).
+    /// Registers with ' are x8..x15.
+    pub fn fields(self) -> &'static [(&'static str, u32, u32)] {
+        match self {
+            CFormat::Cr => &[
+                ("funct4", 15, 12),
+                ("rd/rs1", 11, 7),
+                ("rs2", 6, 2),
+                ("op", 1, 0),
+            ],
+            CFormat::Ci => &[
+                ("funct3", 15, 13),
+                ("imm", 12, 12),
+                ("rd/rs1", 11, 7),
+                ("imm", 6, 2),
+                ("op", 1, 0),
+            ],
+            CFormat::Css => &[
+                ("funct3", 15, 13),
+                ("imm", 12, 7),
+                ("rs2", 6, 2),
+                ("op", 1, 0),
+            ],
+            CFormat::Ciw => &[
+                ("funct3", 15, 13),
+                ("imm", 12, 5),
+                ("rd'", 4, 2),
+                ("op", 1, 0),
+            ],
+            CFormat::Cl => &[
+                ("funct3", 15, 13),
+                ("imm", 12, 10),
+                ("rs1'", 9, 7),
+                ("imm", 6, 5),
+                ("rd'", 4, 2),
+                ("op", 1, 0),
+            ],
+            CFormat::Cs => &[
+                ("funct3", 15, 13),
+                ("imm", 12, 10),
+                ("rs1'", 9, 7),
+                ("imm", 6, 5),
+                ("rs2'", 4, 2),
+                ("op", 1, 0),
+            ],
+            CFormat::Ca => &[
+                ("funct6", 15, 10),
+                ("rd'/rs1'", 9, 7),
+                ("funct2", 6, 5),
+                ("rs2'", 4, 2),
+                ("op", 1, 0),
+            ],
+            CFormat::Cb => &[
+                ("funct3", 15, 13),
+                ("offset", 12, 10),
+                ("rs1'", 9, 7),
+                ("offset", 6, 2),
+                ("op", 1, 0),
+            ],
+            CFormat::Cj => &[("funct3", 15, 13), ("jump target", 12, 2), ("op", 1, 0)],
+        }
+    }
+}
+
+/// Decoded 16-bit instruction
+pub struct CompressedInstr {
+    /// Mnemonic, e.g., "c.addi"
+    pub name: &'static str,
+    pub format: CFormat,
+    /// Equivalent 32-bit instruction
+    pub expanded: u32,
+}
+
+/// Returns true if the lowest 16-bit parcel starts a 16-bit instruction
+pub fn is_compressed(parcel: u32) -> bool {
+    parcel & 0b11 != 0b11
+}
+
+/// Length in bytes of the instruction starting with the parcel
+pub fn instr_len(parcel: u32) -> u64 {
+    if is_compressed(parcel) {
+        2
+    } else {
+        4
+    }
+}
+
+/// 32-bit instruction for the compressed one, 32-bit instructions are returned as is.
+/// Illegal and reserved encodings are returned unchanged.
+pub fn expand(instr: u32) -> u32 {
+    if is_compressed(instr) {
+        match decode_compressed(instr as u16) {
+            Some(c) => c.expanded,
+            None => instr,
+        }
+    } else {
+        instr
+    }
+}
+
+fn enc_r(op: u32, rd: u32, funct3: u32, rs1: u32, rs2: u32, funct7: u32) -> u32 {
+    funct7 << 25 | rs2 << 20 | rs1 << 15 | funct3 << 12 | rd << 7 | op
+}
+
+fn enc_i(op: u32, rd: u32, funct3: u32, rs1: u32, imm: i64) -> u32 {
+    (imm as u32 & 0xfff) << 20 | rs1 << 15 | funct3 << 12 | rd << 7 | op
+}
+
+fn enc_s(op: u32, funct3: u32, rs1: u32, rs2: u32, imm: i64) -> u32 {
+    let imm = imm as u32;
+    bits(imm, 11, 5) << 25 | rs2 << 20 | rs1 << 15 | funct3 << 12 | bits(imm, 4, 0) << 7 | op
+}
+
+fn enc_b(funct3: u32, rs1: u32, rs2: u32, imm: i64) -> u32 {
+    let imm = imm as u32;
+    bits(imm, 12, 12) << 31
+        | bits(imm, 10, 5) << 25
+        | rs2 << 20
+        | rs1 << 15
+        | funct3 << 12
+        | bits(imm, 4, 1) << 8
+        | bits(imm, 11, 11) << 7
+        | opcode::BRANCH
+}
+
+fn enc_j(rd: u32, imm: i64) -> u32 {
+    let imm = imm as u32;
+    bits(imm, 20, 20) << 31
+        | bits(imm, 10, 1) << 21
+        | bits(imm, 11, 11) << 20
+        | bits(imm, 19, 12) << 12
+        | rd << 7
+        | opcode::JAL
+}
+
+/// Decode a 16-bit instruction, None for illegal or reserved encodings
+pub fn decode_compressed(c: u16) -> Option<CompressedInstr> {
+    let c = c as u32;
+    let funct3 = bits(c, 15, 13);
+    // full register fields
+    let rd = bits(c, 11, 7);
+    let rs2 = bits(c, 6, 2);
+    // x8..x15 register fields
+    let rd_p = bits(c, 4, 2) + 8;
+    let rs1_p = bits(c, 9, 7) + 8;
+    // CI immediate imm[5|4:0]
+    let ci_imm = sext(bits(c, 12, 12) << 5 | bits(c, 6, 2), 6);
+    let ci_shamt = bits(c, 12, 12) << 5 | bits(c, 6, 2);
+    // CL/CS offsets of word and double word accesses
+    let lw_off = (bits(c, 12, 10) << 3 | bits(c, 6, 6) << 2 | bits(c, 5, 5) << 6) as i64;
+    let ld_off = (bits(c, 12, 10) << 3 | bits(c, 6, 5) << 6) as i64;
+    const X0: u32 = 0;
+    const RA: u32 = 1;
+    const SP: u32 = 2;
+
+    let (name, format, expanded) = match (c & 0b11, funct3) {
+        (0b00, 0b000) => {
+            let imm = bits(c, 12, 11) << 4
+                | bits(c, 10, 7) << 6
+                | bits(c, 6, 6) << 2
+                | bits(c, 5, 5) << 3;
+            if imm == 0 {
+                return None;
+            }
+            let expanded = enc_i(opcode::OP_IMM, rd_p, 0b000, SP, imm as i64);
+            ("c.addi4spn", CFormat::Ciw, expanded)
+        }
+        (0b00, 0b001) => {
+            let expanded = enc_i(opcode::LOAD_FP, rd_p, 0b011, rs1_p, ld_off);
+            ("c.fld", CFormat::Cl, expanded)
+        }
+        (0b00, 0b010) => {
+            let expanded = enc_i(opcode::LOAD, rd_p, 0b010, rs1_p, lw_off);
+            ("c.lw", CFormat::Cl, expanded)
+        }
+        (0b00, 0b011) => {
+            let expanded = enc_i(opcode::LOAD, rd_p, 0b011, rs1_p, ld_off);
+            ("c.ld", CFormat::Cl, expanded)
+        }
+        (0b00, 0b101) => {
+            let expanded = enc_s(opcode::STORE_FP, 0b011, rs1_p, rd_p, ld_off);
+            ("c.fsd", CFormat::Cs, expanded)
+        }
+        (0b00, 0b110) => {
+            let expanded = enc_s(opcode::STORE, 0b010, rs1_p, rd_p, lw_off);
+            ("c.sw", CFormat::Cs, expanded)
+        }
+        (0b00, 0b111) => {
+            let expanded = enc_s(opcode::STORE, 0b011, rs1_p, rd_p, ld_off);
+            ("c.sd", CFormat::Cs, expanded)
+        }
+        (0b01, 0b000) => {
+            let expanded = enc_i(opcode::OP_IMM, rd, 0b000, rd, ci_imm);
+            let name = if rd == 0 { "c.nop" } else { "c.addi" };
+            (name, CFormat::Ci, expanded)
+        }
+        (0b01, 0b001) => {
+            if rd == 0 {
+                return None;
+            }
+            let expanded = enc_i(opcode::OP_IMM_32, rd, 0b000, rd, ci_imm);
+            ("c.addiw", CFormat::Ci, expanded)
+        }
+        (0b01, 0b010) => {
+            let expanded = enc_i(opcode::OP_IMM, rd, 0b000, X0, ci_imm);
+            ("c.li", CFormat::Ci, expanded)
+        }
+        (0b01, 0b011) if rd == SP => {
+            let imm = bits(c, 12, 12) << 9
+                | bits(c, 6, 6) << 4
+                | bits(c, 5, 5) << 6
+                | bits(c, 4, 3) << 7
+                | bits(c, 2, 2) << 5;
+            if imm == 0 {
+                return None;
+            }
+            let expanded = enc_i(opcode::OP_IMM, SP, 0b000, SP, sext(imm, 10));
+            ("c.addi16sp", CFormat::Ci, expanded)
+        }
+        (0b01, 0b011) => {
+            let imm = sext(bits(c, 12, 12) << 17 | bits(c, 6, 2) << 12, 18);
+            if imm == 0 {
+                return None;
+            }
+            let expanded = (imm as u32 & 0xffff_f000) | rd << 7 | opcode::LUI;
+            ("c.lui", CFormat::Ci, expanded)
+        }
+        (0b01, 0b100) => match (bits(c, 11, 10), bits(c, 12, 12), bits(c, 6, 5)) {
+            (0b00, _, _) => {
+                let expanded = enc_i(opcode::OP_IMM, rs1_p, 0b101, rs1_p, ci_shamt as i64);
+                ("c.srli", CFormat::Cb, expanded)
+            }
+            (0b01, _, _) => {
+                let imm = (0b0100_0000_0000 | ci_shamt) as i64;
+                let expanded = enc_i(opcode::OP_IMM, rs1_p, 0b101, rs1_p, imm);
+                ("c.srai", CFormat::Cb, expanded)
+            }
+            (0b10, _, _) => {
+                let expanded = enc_i(opcode::OP_IMM, rs1_p, 0b111, rs1_p, ci_imm);
+                ("c.andi", CFormat::Cb, expanded)
+            }
+            (0b11, 0, funct2) => {
+                let (name, funct3, funct7) = match funct2 {
+                    0b00 => ("c.sub", 0b000, 0b010_0000),
+                    0b01 => ("c.xor", 0b100, 0),
+                    0b10 => ("c.or", 0b110, 0),
+                    _ => ("c.and", 0b111, 0),
+                };
+                let expanded = enc_r(opcode::OP, rs1_p, funct3, rs1_p, rd_p, funct7);
+                (name, CFormat::Ca, expanded)
+            }
+            (0b11, 1, 0b00) => {
+                let expanded = enc_r(opcode::OP_32, rs1_p, 0b000, rs1_p, rd_p, 0b010_0000);
+                ("c.subw", CFormat::Ca, expanded)
+            }
+            (0b11, 1, 0b01) => {
+                let expanded = enc_r(opcode::OP_32, rs1_p, 0b000, rs1_p, rd_p, 0);
+                ("c.addw", CFormat::Ca, expanded)
+            }
+            _ => return None,
+        },
+        (0b01, 0b101) => {
+            let imm = bits(c, 12, 12) << 11
+                | bits(c, 11, 11) << 4
+                | bits(c, 10, 9) << 8
+                | bits(c, 8, 8) << 10
+                | bits(c, 7, 7) << 6
+                | bits(c, 6, 6) << 7
+                | bits(c, 5, 3) << 1
+                | bits(c, 2, 2) << 5;
+            ("c.j", CFormat::Cj, enc_j(X0, sext(imm, 12)))
+        }
+        (0b01, 0b110 | 0b111) => {
+            let imm = bits(c, 12, 12) << 8
+                | bits(c, 11, 10) << 3
+                | bits(c, 6, 5) << 6
+                | bits(c, 4, 3) << 1
+                | bits(c, 2, 2) << 5;
+            let (name, funct3) = if funct3 == 0b110 {
+                ("c.beqz", 0b000)
+            } else {
+                ("c.bnez", 0b001)
+            };
+            (name, CFormat::Cb, enc_b(funct3, rs1_p, X0, sext(imm, 9)))
+        }
+        (0b10, 0b000) => {
+            let expanded = enc_i(opcode::OP_IMM, rd, 0b001, rd, ci_shamt as i64);
+            ("c.slli", CFormat::Ci, expanded)
+        }
+        (0b10, 0b001) => {
+            let imm = bits(c, 12, 12) << 5 | bits(c, 6, 5) << 3 | bits(c, 4, 2) << 6;
+            let expanded = enc_i(opcode::LOAD_FP, rd, 0b011, SP, imm as i64);
+            ("c.fldsp", CFormat::Ci, expanded)
+        }
+        (0b10, 0b010) => {
+            if rd == 0 {
+                return None;
+            }
+            let imm = bits(c, 12, 12) << 5 | bits(c, 6, 4) << 2 | bits(c, 3, 2) << 6;
+            let expanded = enc_i(opcode::LOAD, rd, 0b010, SP, imm as i64);
+            ("c.lwsp", CFormat::Ci, expanded)
+        }
+        (0b10, 0b011) => {
+            if rd == 0 {
+                return None;
+            }
+            let imm = bits(c, 12, 12) << 5 | bits(c, 6, 5) << 3 | bits(c, 4, 2) << 6;
+            let expanded = enc_i(opcode::LOAD, rd, 0b011, SP, imm as i64);
+            ("c.ldsp", CFormat::Ci, expanded)
+        }
+        (0b10, 0b100) => match (bits(c, 12, 12), rd, rs2) {
+            (0, 0, 0) => return None,
+            (0, _, 0) => ("c.jr", CFormat::Cr, enc_i(opcode::JALR, X0, 0, rd, 0)),
+            (0, _, _) => ("c.mv", CFormat::Cr, enc_r(opcode::OP, rd, 0, X0, rs2, 0)),
+            (_, 0, 0) => ("c.ebreak", CFormat::Cr, 0x0010_0073),
+            (_, _, 0) => ("c.jalr", CFormat::Cr, enc_i(opcode::JALR, RA, 0, rd, 0)),
+            _ => ("c.add", CFormat::Cr, enc_r(opcode::OP, rd, 0, rd, rs2, 0)),
+        },
+        (0b10, 0b101) => {
+            let imm = (bits(c, 12, 10) << 3 | bits(c, 9, 7) << 6) as i64;
+            let expanded = enc_s(opcode::STORE_FP, 0b011, SP, rs2, imm);
+            ("c.fsdsp", CFormat::Css, expanded)
+        }
+        (0b10, 0b110) => {
+            let imm = (bits(c, 12, 9) << 2 | bits(c, 8, 7) << 6) as i64;
+            let expanded = enc_s(opcode::STORE, 0b010, SP, rs2, imm);
+            ("c.swsp", CFormat::Css, expanded)
+        }
+        (0b10, 0b111) => {
+            let imm = (bits(c, 12, 10) << 3 | bits(c, 9, 7) << 6) as i64;
+            let expanded = enc_s(opcode::STORE, 0b011, SP, rs2, imm);
+            ("c.sdsp", CFormat::Css, expanded)
+        }
+        _ => return None,
+    };
+    Some(CompressedInstr {
+        name,
+        format,
+        expanded,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble;
+
+    /// The expansion is the same as the assembled 32-bit instruction
+    fn expands_to(c: u16, text: &str) {
+        let expanded = decode_compressed(c).map(|c| c.expanded);
+        assert_eq!(expanded, Some(assemble(text).unwrap()), "{c:04x} {text}");
+    }
+
+    #[test]
+    fn expansion() {
+        expands_to(0x0505, "addi a0, a0, 1");
+        expands_to(0x70fe, "ld ra, 504(sp)");
+        expands_to(0x7505, "lui a0, 0xfffe1");
+        expands_to(0x3501, "addiw a0, a0, -32");
+    }
+
+    #[test]
+    fn jump_and_branch_extremes() {
+        expands_to(0xb001, "jal zero, -2048");
+        expands_to(0xaffd, "jal zero, 2046");
+        expands_to(0xd101, "beq a0, zero, -256");
+        expands_to(0xecfd, "bne s1, zero, 254");
+    }
+
+    #[test]
+    fn lengths() {
+        assert!(decode_compressed(0).is_none());
+        assert_eq!(instr_len(0x0505), 2);
+        assert_eq!(instr_len(0x0015_0513), 4);
+        assert_eq!(expand(0x0505), 0x0015_0513);
+        assert_eq!(expand(0x0015_0513), 0x0015_0513);
+    }
+}
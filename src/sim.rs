@@ -1,15 +1,108 @@
 use std::{
-    sync::mpsc::{self, Receiver, RecvError, Sender, TryRecvError},
+    ops::Range,
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
     thread,
 };
 
+use crate::{breakpoints::SimBreakpoint, fdt};
 use kompusim::{bus, device::Device, ram, rv64i_cpu::RV64ICpu, uart::Uart};
 
+/// Memory map of the simulated machine
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MachineConfig {
+    pub ram_base: u64,
+    pub ram_size: u64,
+    pub uart_base: u64,
+    /// Where to place the generated device tree blob, None - no device tree
+    pub dtb_addr: Option<u64>,
+}
+
+impl MachineConfig {
+    /// 4 KiB of RAM at 0x80000000, UART at 0x10010000, no device tree
+    pub const DEFAULT: MachineConfig = MachineConfig {
+        ram_base: 0x0000000080000000,
+        ram_size: 4 * 1024,
+        uart_base: 0x1001_0000,
+        dtb_addr: None,
+    };
+
+    /// RAM is grown to fit loaded images up to this size
+    pub const MAX_RAM_SIZE: u64 = 256 * 1024 * 1024;
+
+    /// The DTB is moved to the first page after the image if it doesn't fit where it is
+    const DTB_ALIGN: u64 = 0x1000;
+
+    /// Fit the device tree blob into RAM next to the image: grow RAM up to MAX_RAM_SIZE
+    /// or move the DTB after the image if it is outside of RAM or overlaps the image.
+    /// Returns a note if the DTB was moved, an error if it doesn't fit (the machine
+    /// gets no device tree then).
+    pub fn place_dtb(&mut self, image: Range<u64>) -> Result<Option<String>, String> {
+        let addr = match self.dtb_addr {
+            Some(addr) => addr,
+            None => return Ok(None),
+        };
+        let len = fdt::generate(self).len() as u64;
+        let ram_limit = self.ram_base + Self::MAX_RAM_SIZE;
+        let overlaps = addr < image.end && image.start < addr.saturating_add(len);
+        let in_ram = addr >= self.ram_base && addr.saturating_add(len) <= ram_limit;
+        let (new_addr, note) = if overlaps || !in_ram {
+            let reason = if overlaps {
+                "overlaps the image"
+            } else {
+                "is outside of RAM"
+            };
+            let new_addr = image
+                .end
+                .max(self.ram_base)
+                .next_multiple_of(Self::DTB_ALIGN);
+            let note = format!("the DTB at 0x{addr:x} {reason}, moved to 0x{new_addr:x}");
+            (new_addr, Some(note))
+        } else {
+            (addr, None)
+        };
+        if new_addr.saturating_add(len) > ram_limit {
+            self.dtb_addr = None;
+            return Err(format!(
+                "the DTB at 0x{addr:x} doesn't fit into {} MiB of RAM with the image, \
+                 the machine has no device tree",
+                Self::MAX_RAM_SIZE / (1024 * 1024)
+            ));
+        }
+        self.dtb_addr = Some(new_addr);
+        let dtb_end = new_addr + len - self.ram_base;
+        self.ram_size = self.ram_size.max(dtb_end.next_multiple_of(0x1000));
+        Ok(note)
+    }
+}
+
+impl Default for MachineConfig {
+    fn default() -> MachineConfig {
+        MachineConfig::DEFAULT
+    }
+}
+
 pub struct Simulator {
     sim_thread: Option<thread::JoinHandle<()>>,
     cmd_channel: Sender<SimCommand>,
     /// UART TX receive queue
     uart_tx_recv: Receiver<u8>,
+    /// Events from the simulator thread
+    event_recv: Receiver<SimEvent>,
+}
+
+/// Messages from the simulator thread to the GUI
+pub enum SimEvent {
+    /// Memory content read on request
+    Memory { addr: u64, data: Vec<u8> },
+    /// Execution stopped (breakpoint, step, pause or reset),
+    /// `stepped` - exactly one instruction was executed, `x` - integer registers
+    Stopped {
+        pc: u64,
+        stepped: bool,
+        x: Box<[u64; 32]>,
+    },
+    /// Execution continued, register values are not known until it stops
+    Running,
 }
 
 #[derive(PartialEq)]
@@ -18,10 +111,16 @@ enum SimState {
     Running,
 }
 enum SimCommand {
-    //Reset,
+    Reset(MachineConfig),
     //Init,
-    LoadImage((u64, &'static [u8])),
+    LoadImage((u64, Vec<u8>)),
+    SetPc(u64),
+    SetBreakpoints(Vec<SimBreakpoint>),
+    /// Read memory (address, length in bytes)
+    ReadMemory((u64, u64)),
     Continue,
+    Step,
+    Pause,
     Stop,
     NoCmd,
 }
@@ -30,25 +129,20 @@ impl Simulator {
     pub fn new() -> Self {
         let (cmd_tx, cmd_rx): (Sender<SimCommand>, Receiver<SimCommand>) = mpsc::channel();
         let (uart_tx_send, uart_tx_recv): (Sender<u8>, Receiver<u8>) = mpsc::channel();
+        let (event_send, event_recv): (Sender<SimEvent>, Receiver<SimEvent>) = mpsc::channel();
 
         // Start the simulator thread
         let sim_thread_handler = thread::spawn(move || {
-            let addr = 0x0000000080000000; // TODO: remove
-            let ram_sz = 4 * 1024; // TODO: remove
-            let ram = ram::Ram::new(addr, ram_sz);
-            let mut bus = bus::Bus::new();
-            bus.attach_ram(ram);
-
-            let mut uart0 = Box::new(Uart::new("0".to_string()));
-            uart0.register_out_callback(Box::new(move |b: u8| {
-                if let Err(err) = uart_tx_send.send(b) {
-                    println!("Simulator: failed to send command: {}", err);
-                }
-            }));
-            bus.attach_device(Device::new(uart0, 0x1001_0000, 0x20));
+            let mut machine = MachineConfig::default();
+            let mut cpu0 = build_machine(&machine, &uart_tx_send);
+            // breakpoints survive machine resets
+            let mut breakpoints: Vec<SimBreakpoint> = Vec::new();
 
-            let mut cpu0 = RV64ICpu::new(bus);
-            cpu0.regs.pc = addr;
+            let send_event = |event: SimEvent| {
+                if let Err(err) = event_send.send(event) {
+                    println!("Simulator: failed to send event: {}", err);
+                }
+            };
 
             let mut sim_state = SimState::Stopped;
             loop {
@@ -69,22 +163,85 @@ impl Simulator {
                     }
                 };
                 match recv_cmd {
-                    // SimCommand::Reset => {
-                    //     println!("Simulator: reset command")
-                    // }
+                    SimCommand::Reset(config) => {
+                        sim_state = SimState::Stopped;
+                        cpu0 = build_machine(&config, &uart_tx_send);
+                        println!("Simulator: reset with {:x?}", config);
+                        machine = config;
+                        send_event(SimEvent::Stopped {
+                            pc: cpu0.regs.pc,
+                            stepped: false,
+                            x: Box::new(cpu0.regs.x),
+                        });
+                    }
                     //SimCommand::Init => {}
                     SimCommand::LoadImage((load_addr, image)) => {
-                        cpu0.bus.load_image(load_addr, image).unwrap();
-                        println!("Simulator: image loaded at 0x{:x}", load_addr);
+                        match cpu0.bus.load_image(load_addr, &image) {
+                            Ok(_) => println!("Simulator: image loaded at 0x{:x}", load_addr),
+                            Err(err) => println!(
+                                "Simulator: failed to load image at 0x{:x}: {:?}",
+                                load_addr, err
+                            ),
+                        }
+                    }
+                    SimCommand::SetPc(pc) => {
+                        cpu0.regs.pc = pc;
+                        send_event(SimEvent::Stopped {
+                            pc,
+                            stepped: false,
+                            x: Box::new(cpu0.regs.x),
+                        });
+                    }
+                    SimCommand::SetBreakpoints(new_breakpoints) => {
+                        breakpoints = new_breakpoints;
+                    }
+                    SimCommand::ReadMemory((addr, len)) => {
+                        let (addr, data) = read_ram(&mut cpu0, &machine, addr, len);
+                        send_event(SimEvent::Memory { addr, data });
                     }
                     SimCommand::Continue => {
                         sim_state = SimState::Running;
-                        let _ = cpu0.exec_continue(1024);
+                        send_event(SimEvent::Running);
+                        // don't stop at the breakpoint we are continuing from
+                        if exec_instructions(&mut cpu0, &breakpoints, 1024, true) {
+                            sim_state = SimState::Stopped;
+                            send_event(SimEvent::Stopped {
+                                pc: cpu0.regs.pc,
+                                stepped: false,
+                                x: Box::new(cpu0.regs.x),
+                            });
+                        }
+                    }
+                    SimCommand::Step => {
+                        sim_state = SimState::Stopped;
+                        let _ = cpu0.exec_continue(1);
+                        send_event(SimEvent::Stopped {
+                            pc: cpu0.regs.pc,
+                            stepped: true,
+                            x: Box::new(cpu0.regs.x),
+                        });
+                    }
+                    SimCommand::Pause => {
+                        if sim_state == SimState::Running {
+                            sim_state = SimState::Stopped;
+                            send_event(SimEvent::Stopped {
+                                pc: cpu0.regs.pc,
+                                stepped: false,
+                                x: Box::new(cpu0.regs.x),
+                            });
+                        }
                     }
                     SimCommand::Stop => break,
                     SimCommand::NoCmd => {
-                        if sim_state == SimState::Running {
-                            let _ = cpu0.exec_continue(1024);
+                        if sim_state == SimState::Running
+                            && exec_instructions(&mut cpu0, &breakpoints, 1024, false)
+                        {
+                            sim_state = SimState::Stopped;
+                            send_event(SimEvent::Stopped {
+                                pc: cpu0.regs.pc,
+                                stepped: false,
+                                x: Box::new(cpu0.regs.x),
+                            });
                         }
                     }
                 }
@@ -97,6 +254,7 @@ impl Simulator {
             sim_thread: Some(sim_thread_handler),
             cmd_channel: cmd_tx,
             uart_tx_recv,
+            event_recv,
         }
     }
 
@@ -110,20 +268,59 @@ impl Simulator {
     }
 
     fn send_cmd(&self, cmd: SimCommand) {
-        match self.cmd_channel.send(cmd) {
-            Err(e) => println!("FAILED to send command. Error: {}", e),
-            Ok(_) => {}
+        if let Err(e) = self.cmd_channel.send(cmd) {
+            println!("FAILED to send command. Error: {}", e)
         }
     }
-    pub fn load_image(&mut self, addr: u64, image: &'static [u8]) {
+    /// Rebuild the machine from scratch with the given memory map
+    pub fn reset(&mut self, config: MachineConfig) {
+        self.send_cmd(SimCommand::Reset(config));
+    }
+
+    pub fn load_image(&mut self, addr: u64, image: Vec<u8>) {
         self.send_cmd(SimCommand::LoadImage((addr, image)));
     }
 
+    pub fn set_pc(&mut self, pc: u64) {
+        self.send_cmd(SimCommand::SetPc(pc));
+    }
+
+    /// Replace all breakpoints
+    pub fn set_breakpoints(&mut self, breakpoints: Vec<SimBreakpoint>) {
+        self.send_cmd(SimCommand::SetBreakpoints(breakpoints));
+    }
+
+    /// Request memory content, the result arrives as SimEvent::Memory.
+    /// Only the part of the range inside RAM is read.
+    pub fn read_memory(&self, addr: u64, len: u64) {
+        self.send_cmd(SimCommand::ReadMemory((addr, len)));
+    }
+
+    pub fn event_recv(&self) -> Option<SimEvent> {
+        match self.event_recv.try_recv() {
+            Ok(event) => Some(event),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                println!("Simulator: FATAL ERROR: got Disconnected on event receive attempt");
+                None
+            }
+        }
+    }
+
     // continue is a Rust keyword, so use carry_on()
     pub fn carry_on(&self) {
         self.cmd_channel.send(SimCommand::Continue).unwrap();
     }
 
+    /// Execute one instruction
+    pub fn step(&self) {
+        self.send_cmd(SimCommand::Step);
+    }
+
+    pub fn pause(&self) {
+        self.send_cmd(SimCommand::Pause);
+    }
+
     pub fn console_recv(&self) -> Option<String> {
         // TODO: pass &String and push to it instead of allocating every time
         let mut new_bytes = String::new();
@@ -137,10 +334,143 @@ impl Simulator {
                 }
             }
         }
-        if new_bytes.len() > 0 {
+        if !new_bytes.is_empty() {
             Some(new_bytes)
         } else {
             None
         }
     }
 }
+
+/// Execute up to max_instr instructions.
+/// Returns true if the execution stopped at a breakpoint.
+fn exec_instructions(
+    cpu0: &mut RV64ICpu,
+    breakpoints: &[SimBreakpoint],
+    max_instr: u64,
+    skip_first_bp: bool,
+) -> bool {
+    if breakpoints.is_empty() {
+        let _ = cpu0.exec_continue(max_instr);
+        return false;
+    }
+    for i in 0..max_instr {
+        if (i > 0 || !skip_first_bp) && breakpoint_hit(cpu0, breakpoints) {
+            println!("Simulator: stopped at breakpoint 0x{:x}", cpu0.regs.pc);
+            return true;
+        }
+        let _ = cpu0.exec_continue(1);
+    }
+    false
+}
+
+fn breakpoint_hit(cpu0: &RV64ICpu, breakpoints: &[SimBreakpoint]) -> bool {
+    breakpoints.iter().any(|bp| {
+        bp.addr == cpu0.regs.pc
+            && bp
+                .condition
+                .as_ref()
+                .is_none_or(|cond| cond.eval(&cpu0.regs.x))
+    })
+}
+
+/// Read [addr, addr + len) clipped to RAM, returns the address of the first byte and the bytes
+fn read_ram(cpu0: &mut RV64ICpu, machine: &MachineConfig, addr: u64, len: u64) -> (u64, Vec<u8>) {
+    let start = addr.max(machine.ram_base);
+    let end = addr
+        .saturating_add(len)
+        .min(machine.ram_base.saturating_add(machine.ram_size));
+    let mut data = Vec::with_capacity(end.saturating_sub(start) as usize);
+    let mut addr = start;
+    while addr.checked_add(4).is_some_and(|next| next <= end) {
+        data.extend_from_slice(&cpu0.bus.read32(addr).to_le_bytes());
+        addr += 4;
+    }
+    // the unaligned tail
+    while addr < end {
+        data.push(cpu0.bus.read8(addr));
+        addr += 1;
+    }
+    (start, data)
+}
+
+/// Create RAM, UART and CPU according to the machine configuration.
+/// The CPU starts at the beginning of RAM with hart ID in a0 and
+/// the device tree address (if any) in a1.
+fn build_machine(config: &MachineConfig, uart_tx_send: &Sender<u8>) -> RV64ICpu {
+    let ram = ram::Ram::new(config.ram_base, config.ram_size);
+    let mut bus = bus::Bus::new();
+    bus.attach_ram(ram);
+
+    let uart_tx_send = uart_tx_send.clone();
+    let mut uart0 = Box::new(Uart::new("0".to_string()));
+    uart0.register_out_callback(Box::new(move |b: u8| {
+        if let Err(err) = uart_tx_send.send(b) {
+            println!("Simulator: failed to send command: {}", err);
+        }
+    }));
+    bus.attach_device(Device::new(uart0, config.uart_base, 0x20));
+
+    let mut cpu0 = RV64ICpu::new(bus);
+    cpu0.regs.pc = config.ram_base;
+    cpu0.regs.x[10] = 0; // a0: hart ID
+    if let Some(dtb_addr) = config.dtb_addr {
+        let dtb = fdt::generate(config);
+        match cpu0.bus.load_image(dtb_addr, &dtb) {
+            Ok(_) => cpu0.regs.x[11] = dtb_addr, // a1: device tree address
+            Err(err) => println!(
+                "Simulator: failed to load DTB at 0x{:x}: {:?}",
+                dtb_addr, err
+            ),
+        }
+    }
+    cpu0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RAM_BASE: u64 = MachineConfig::DEFAULT.ram_base;
+
+    fn machine(dtb_addr: u64) -> MachineConfig {
+        MachineConfig {
+            dtb_addr: Some(dtb_addr),
+            ..MachineConfig::DEFAULT
+        }
+    }
+
+    #[test]
+    fn dtb_after_ram_grows_ram() {
+        // a demo machine's DTB address kept for a binary in the 4 KiB default RAM
+        let mut config = machine(RAM_BASE + 0x1000);
+        let note = config.place_dtb(RAM_BASE..RAM_BASE + 0x100).unwrap();
+        assert_eq!(note, None);
+        assert_eq!(config.dtb_addr, Some(RAM_BASE + 0x1000));
+        assert_eq!(config.ram_size, 0x2000);
+    }
+
+    #[test]
+    fn dtb_overlapping_the_image_moves() {
+        let mut config = machine(RAM_BASE + 0x100);
+        let note = config.place_dtb(RAM_BASE..RAM_BASE + 0x1800).unwrap();
+        assert!(note.is_some());
+        assert_eq!(config.dtb_addr, Some(RAM_BASE + 0x2000));
+        assert_eq!(config.ram_size, 0x3000);
+    }
+
+    #[test]
+    fn dtb_outside_of_ram_moves() {
+        let mut config = machine(0x1000);
+        assert!(config
+            .place_dtb(RAM_BASE..RAM_BASE + 0x10)
+            .unwrap()
+            .is_some());
+        assert_eq!(config.dtb_addr, Some(RAM_BASE + 0x1000));
+
+        let image_end = RAM_BASE + MachineConfig::MAX_RAM_SIZE;
+        let mut config = machine(RAM_BASE);
+        assert!(config.place_dtb(RAM_BASE..image_end).is_err());
+        assert_eq!(config.dtb_addr, None);
+    }
+}